@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ffmpeg::{
+    check_output, geometry_filters, get_target_bytes, input_args, progress_args, run_pass,
+    shrink_until_fits, ConversionError, ConversionProgress, ConversionStage, PixelCrop,
+    ProgressReporter,
+};
+use crate::jobs::JobHandle;
+
+/// How many times the animation is encoded at most while trying to fit the target size
+pub const MAX_ANIMATION_ATTEMPTS: u32 = 6;
+
+/// The first attempt never goes above these, chats show animations small anyway
+const START_FPS: f32 = 15.0;
+const START_WIDTH: u32 = 480;
+const START_QUALITY: u32 = 75;
+
+/// Below these an animation is not worth sending
+const MIN_FPS: f32 = 5.0;
+const MIN_WIDTH: u32 = 96;
+const MIN_QUALITY: u32 = 30;
+const MIN_COLORS: u32 = 64;
+
+/// Misses smaller than this are fixed with colors, dithering or quality before the size is touched
+const SMALL_MISS: f64 = 1.35;
+
+/// Settings aim this much below the target, sizes of animations are hard to predict
+const ANIMATION_SAFETY: f64 = 0.90;
+
+/// Animated outputs for chats that want images instead of videos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationFormat {
+    Gif,
+    Webp,
+}
+
+impl AnimationFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Webp => "webp",
+        }
+    }
+
+    /// ffmpeg encoders for this format, the preferred one first
+    fn encoders(&self) -> &'static [&'static str] {
+        match self {
+            AnimationFormat::Gif => &["gif"],
+            AnimationFormat::Webp => &["libwebp_anim", "libwebp"],
+        }
+    }
+
+    /// First encoder of this format the bundled ffmpeg was built with
+    pub fn find_encoder(&self, available: &[String]) -> Result<&'static str, ConversionError> {
+        self.encoders()
+            .iter()
+            .copied()
+            .find(|encoder| available.iter().any(|a| a == encoder))
+            .ok_or(ConversionError::EncoderUnavailable {
+                encoder: self.encoders()[0].to_string(),
+            })
+    }
+}
+
+/// Error diffusion looks better, ordered dithering compresses a lot better in a GIF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Dither {
+    Sierra,
+    Bayer,
+}
+
+impl Dither {
+    fn filter(&self) -> &'static str {
+        match self {
+            Dither::Sierra => "sierra2_4a",
+            Dither::Bayer => "bayer:bayer_scale=3",
+        }
+    }
+}
+
+/// What an animation attempt is encoded with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnimationSettings {
+    pub fps: f32,
+    pub width: u32,
+    /// palette size, GIF only
+    pub colors: u32,
+    /// GIF only
+    pub dither: Dither,
+    /// 0-100, WebP only
+    pub quality: u32,
+}
+
+impl AnimationSettings {
+    /// First attempt, the source is never upscaled or sped up
+    pub fn initial(source_width: u32, source_fps: Option<f32>) -> Self {
+        let source_fps = source_fps.filter(|fps| *fps > 0.0).unwrap_or(START_FPS);
+        AnimationSettings {
+            fps: START_FPS.min(source_fps),
+            width: START_WIDTH.min(source_width.max(MIN_WIDTH)),
+            colors: 256,
+            dither: Dither::Sierra,
+            quality: START_QUALITY,
+        }
+    }
+
+    /// Settings for the next attempt after an output `ratio` times the target.
+    /// Small misses lower colors, dithering or quality first, bigger ones shrink the
+    /// frame size and frame rate together. `None` when nothing can be lowered anymore.
+    pub fn next(&self, format: AnimationFormat, ratio: f64) -> Option<Self> {
+        let mut next = *self;
+        let small_miss = ratio < SMALL_MISS;
+
+        match format {
+            AnimationFormat::Gif => {
+                if self.dither == Dither::Sierra {
+                    next.dither = Dither::Bayer;
+                    if small_miss {
+                        return Some(next);
+                    }
+                }
+                if small_miss && self.colors > MIN_COLORS {
+                    next.colors = (self.colors / 2).max(MIN_COLORS);
+                    return Some(next);
+                }
+            }
+            AnimationFormat::Webp => {
+                if small_miss && self.quality > MIN_QUALITY {
+                    next.quality = self.quality.saturating_sub(15).max(MIN_QUALITY);
+                    return Some(next);
+                }
+            }
+        }
+
+        // Boyut kabaca genişlik² × fps ile orantılı, fark üçüne eşit paylaştırılır
+        let step = (ANIMATION_SAFETY / ratio).powf(1.0 / 3.0);
+        next.width = (((self.width as f64 * step).round() as u32) & !1).max(MIN_WIDTH);
+        next.fps = ((self.fps as f64 * step).round() as f32).max(MIN_FPS);
+
+        if next == *self {
+            None
+        } else {
+            Some(next)
+        }
+    }
+}
+
+/// One attempt of a GIF or animated WebP export
+pub struct AnimationEncode {
+    pub input: String,
+    pub output: String,
+    pub format: AnimationFormat,
+    /// see `AnimationFormat::find_encoder`
+    pub encoder: String,
+    pub start_time: Option<f32>,
+    pub end_time: Option<f32>,
+    pub crop: Option<PixelCrop>,
+    /// see `geometry_filters`
+    pub sar_scale: Option<(u32, u32)>,
+    pub settings: AnimationSettings,
+}
+
+impl AnimationEncode {
+    /// Runs the encode, returns the size of the output in bytes
+    pub fn run(
+        &self,
+        progress: &mut ProgressReporter,
+        job: &JobHandle,
+    ) -> Result<u64, ConversionError> {
+        let args = self.args();
+        eprintln!("Animasyon FFmpeg komutu: {}", args.join(" "));
+        run_pass(1, args, progress, job)?;
+
+        progress.report(ConversionProgress::verifying());
+        check_output(&self.output)
+    }
+
+    /// Crop, frame rate and size, the same for both formats
+    fn filters(&self) -> String {
+        let mut filters = geometry_filters(self.sar_scale, self.crop);
+        filters.push(format!("fps={}", self.settings.fps));
+        filters.push(format!("scale={}:-1:flags=lanczos", self.settings.width));
+        filters.join(",")
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = progress_args();
+        args.extend(input_args(&self.input, self.start_time, self.end_time));
+
+        let settings = self.settings;
+        match self.format {
+            AnimationFormat::Gif => {
+                // Palet sadece değişen piksellerden çıkarılır, hareketli kısımlar daha az bantlanır
+                let graph = format!(
+                    "[0:v:0]{},split[frames][source];\
+                     [source]palettegen=max_colors={}:stats_mode=diff[palette];\
+                     [frames][palette]paletteuse=dither={}:diff_mode=rectangle",
+                    self.filters(),
+                    settings.colors,
+                    settings.dither.filter()
+                );
+                args.extend(vec!["-filter_complex".to_string(), graph]);
+            }
+            AnimationFormat::Webp => {
+                args.extend(vec![
+                    "-map".to_string(),
+                    "0:v:0".to_string(),
+                    "-filter:v".to_string(),
+                    self.filters(),
+                    "-c:v".to_string(),
+                    self.encoder.clone(),
+                    "-lossless".to_string(),
+                    "0".to_string(),
+                    "-q:v".to_string(),
+                    settings.quality.to_string(),
+                    "-compression_level".to_string(),
+                    "6".to_string(),
+                ]);
+            }
+        }
+
+        args.extend(vec![
+            "-loop".to_string(),
+            "0".to_string(),
+            "-an".to_string(),
+            "-f".to_string(),
+            self.format.extension().to_string(),
+            self.output.clone(),
+        ]);
+        args
+    }
+}
+
+/// Encodes the animation, lowering the settings after every attempt that came out
+/// too big, up to `MAX_ANIMATION_ATTEMPTS` times. Returns the final size in bytes.
+pub fn encode_animation_to_size(
+    encode: &mut AnimationEncode,
+    target_size: f32,
+    progress: &mut ProgressReporter,
+    job: &JobHandle,
+) -> Result<u64, ConversionError> {
+    let target_bytes = get_target_bytes(target_size);
+
+    shrink_until_fits(
+        encode,
+        target_bytes,
+        MAX_ANIMATION_ATTEMPTS,
+        Some(ConversionStage::Encoding),
+        progress,
+        |encode, progress| {
+            eprintln!("Animasyon ayarları: {:?}", encode.settings);
+            encode.run(progress, job)
+        },
+        |encode, size| {
+            let ratio = size as f64 / target_bytes as f64;
+            match encode.settings.next(encode.format, ratio) {
+                Some(next) => {
+                    encode.settings = next;
+                    true
+                }
+                None => false,
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smallest() -> AnimationSettings {
+        AnimationSettings {
+            fps: MIN_FPS,
+            width: MIN_WIDTH,
+            colors: MIN_COLORS,
+            dither: Dither::Bayer,
+            quality: MIN_QUALITY,
+        }
+    }
+
+    #[test]
+    fn initial_never_upscales_or_speeds_up() {
+        let settings = AnimationSettings::initial(1920, Some(60.0));
+        assert_eq!((settings.width, settings.fps), (START_WIDTH, START_FPS));
+
+        let settings = AnimationSettings::initial(320, Some(10.0));
+        assert_eq!((settings.width, settings.fps), (320, 10.0));
+
+        let settings = AnimationSettings::initial(50, None);
+        assert_eq!((settings.width, settings.fps), (MIN_WIDTH, START_FPS));
+    }
+
+    #[test]
+    fn small_gif_misses_lower_dither_and_colors_first() {
+        let initial = AnimationSettings::initial(1920, Some(30.0));
+
+        let next = initial.next(AnimationFormat::Gif, 1.2).unwrap();
+        assert_eq!(next.dither, Dither::Bayer);
+        assert_eq!((next.width, next.fps, next.colors), (480, 15.0, 256));
+
+        let next = next.next(AnimationFormat::Gif, 1.2).unwrap();
+        assert_eq!((next.width, next.colors), (480, 128));
+
+        let next = next.next(AnimationFormat::Gif, 1.2).unwrap();
+        assert_eq!((next.width, next.colors), (480, MIN_COLORS));
+
+        // Renk düşürülemeyince boyut küçülür
+        let next = next.next(AnimationFormat::Gif, 1.2).unwrap();
+        assert_eq!((next.width, next.fps, next.colors), (436, 14.0, MIN_COLORS));
+    }
+
+    #[test]
+    fn big_misses_shrink_size_and_fps() {
+        let initial = AnimationSettings::initial(1920, Some(30.0));
+
+        let next = initial.next(AnimationFormat::Gif, 2.0).unwrap();
+        assert_eq!(next.dither, Dither::Bayer);
+        assert_eq!((next.width, next.fps, next.colors), (368, 11.0, 256));
+
+        let next = initial.next(AnimationFormat::Webp, 2.0).unwrap();
+        assert_eq!(
+            (next.width, next.fps, next.quality),
+            (368, 11.0, START_QUALITY)
+        );
+    }
+
+    #[test]
+    fn small_webp_misses_lower_quality_first() {
+        let initial = AnimationSettings::initial(1920, Some(30.0));
+        let next = initial.next(AnimationFormat::Webp, 1.2).unwrap();
+        assert_eq!((next.width, next.fps, next.quality), (480, 15.0, 60));
+    }
+
+    #[test]
+    fn ladder_ends_at_the_smallest_settings() {
+        for format in [AnimationFormat::Gif, AnimationFormat::Webp] {
+            assert_eq!(smallest().next(format, 1.1), None);
+            assert_eq!(smallest().next(format, 5.0), None);
+        }
+
+        // Her adım bir şeyi düşürür, sonunda deneyecek ayar kalmaz
+        let mut settings = AnimationSettings::initial(1920, Some(60.0));
+        let mut steps = 0;
+        while let Some(next) = settings.next(AnimationFormat::Gif, 3.0) {
+            settings = next;
+            steps += 1;
+            assert!(steps < 50);
+        }
+        assert_eq!((settings.width, settings.fps), (MIN_WIDTH, MIN_FPS));
+    }
+}
@@ -0,0 +1,352 @@
+use atem::codec::{AudioCodec, VideoCodec};
+use atem::ffmpeg::{Clip, ConversionError, ConversionProgress, ConversionRequest, TimeRange};
+use atem::history::ConversionDetails;
+use atem::jobs::{new_job_id, JobHandle};
+use atem::output::OutputPolicy;
+use atem::presets::PresetRegistry;
+use atem::settings::Settings;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::api::cli::{get_matches, Matches};
+use tauri::{Config, PackageInfo};
+
+use crate::{log_error, log_info, run_conversion};
+
+/// Every input was converted
+pub const EXIT_OK: i32 = 0;
+/// At least one input failed to convert
+pub const EXIT_FAILED: i32 = 1;
+/// Bad arguments or no input matched
+pub const EXIT_USAGE: i32 = 2;
+
+/// Headless mode, `max8 input.mp4 --size 8 --start 10 --end 40 --out dir/` runs the same
+/// conversion as the editor without opening a window.
+/// Returns the exit code, or `None` when the editor should open: no input was given, or the
+/// arguments could not be parsed and none of them was an option (see `cli_requested`).
+pub fn run_from_args(
+    config: &Config,
+    package_info: &PackageInfo,
+    settings: &Settings,
+    presets: &PresetRegistry,
+) -> Option<i32> {
+    let cli = config.tauri.cli.as_ref()?;
+
+    let matches = match get_matches(cli, package_info) {
+        Ok(matches) => matches,
+        Err(e) if cli_requested() => {
+            attach_console();
+            eprintln!("{}", e);
+            return Some(EXIT_USAGE);
+        }
+        // Başlatıcının eklediği bilinmeyen argümanlar editörün açılmasını engellemesin
+        Err(e) => {
+            log_error(&format!("Komut satırı okunamadı, editör açılıyor: {}", e), "CLI");
+            return None;
+        }
+    };
+
+    // Konsol sadece komut satırı modunda bağlanır, editör konsolsuz açılır
+    if let Some(Value::String(help)) = matches.args.get("help").map(|arg| &arg.value) {
+        attach_console();
+        println!("{}", help);
+        return Some(EXIT_OK);
+    }
+    if matches.args.contains_key("version") {
+        attach_console();
+        println!("{} {}", package_info.name, package_info.version);
+        return Some(EXIT_OK);
+    }
+
+    let patterns = strings(&matches, "path");
+    if patterns.is_empty() {
+        return None;
+    }
+
+    attach_console();
+    Some(run(&matches, &patterns, settings, presets))
+}
+
+/// True when an option was given on the command line. Launchers can add arguments
+/// of their own (`-psn_...` on older macOS), those alone still open the editor.
+fn cli_requested() -> bool {
+    std::env::args()
+        .skip(1)
+        .any(|arg| arg.starts_with('-') && !arg.starts_with("-psn_"))
+}
+
+/// Release builds on Windows use the GUI subsystem and get no console, output goes
+/// nowhere unless the console of the shell that started the app is attached
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    // Çift tıklamayla açıldıysa bağlanacak konsol yoktur, hata yok sayılır
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+fn run(matches: &Matches, patterns: &[String], settings: &Settings, presets: &PresetRegistry) -> i32 {
+    let request = match parse_request(matches, settings, presets) {
+        Ok(request) => request,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return EXIT_USAGE;
+        }
+    };
+
+    let inputs = expand_inputs(patterns);
+    if inputs.is_empty() {
+        eprintln!("error: no input matched {}", patterns.join(" "));
+        return EXIT_USAGE;
+    }
+
+    log_info(&format!("Komut satırından {} video dönüştürülecek", inputs.len()), "CLI");
+
+    // --merge ile tüm videolar sırayla tek çıktıda birleştirilir
+    let requests: Vec<ConversionRequest> = if flag(matches, "merge") {
+        vec![ConversionRequest {
+            input: inputs[0].clone(),
+            clips: inputs
+                .iter()
+                .map(|input| Clip {
+                    input: input.clone(),
+                    start_time: None,
+                    end_time: None,
+                })
+                .collect(),
+            ..request
+        }]
+    } else {
+        inputs
+            .iter()
+            .map(|input| ConversionRequest {
+                input: input.clone(),
+                ..request.clone()
+            })
+            .collect()
+    };
+
+    let mut failed = 0;
+    for (i, request) in requests.iter().enumerate() {
+        let input = &request.input;
+        let label = format!("[{}/{}]", i + 1, requests.len());
+        println!("{} {}", label, input);
+
+        let job_id = format!("{}-{}", new_job_id(), i);
+        let job = Arc::new(JobHandle::default());
+
+        // Her yüzde değişiminde değil, tam sayı yüzde ve geçiş değişince yazdır
+        let mut last = (0, -1);
+        let mut details = ConversionDetails::default();
+        let result = run_conversion(&job, &job_id, request, &mut details, |p: ConversionProgress| {
+            let current = (p.pass, p.percent.floor() as i32);
+            if current == last {
+                return;
+            }
+            last = current;
+
+            let eta = p
+                .eta
+                .map(|eta| format!(" eta {}s", eta.round()))
+                .unwrap_or_default();
+            println!(
+                "{} pass {} attempt {} {:>3}%{}",
+                label, p.pass, p.attempt, current.1, eta
+            );
+        });
+
+        match result {
+            Ok(output) => println!("{} done: {}", label, output),
+            // --on-exists skip ile atlanan dosya hata sayılmaz
+            Err(ConversionError::OutputExists { path }) => {
+                println!("{} skipped, {} exists", label, path)
+            }
+            Err(e) => {
+                failed += 1;
+                log_error(&format!("{} dönüştürülemedi: {}", input, e), "CLI");
+                eprintln!("{} error: {}: {}", label, input, e);
+                if let Some(stderr) = e.stderr() {
+                    eprintln!("{}", stderr);
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{} of {} conversions failed", failed, requests.len());
+        EXIT_FAILED
+    } else {
+        EXIT_OK
+    }
+}
+
+/// Everything but the input, shared by all inputs of the run.
+/// Options that are not given come from the saved settings, or from the preset.
+fn parse_request(
+    matches: &Matches,
+    settings: &Settings,
+    presets: &PresetRegistry,
+) -> Result<ConversionRequest, String> {
+    let preset = match string(matches, "preset") {
+        Some(id) => Some(presets.get(&id).map_err(|e| e.to_string())?),
+        None => None,
+    };
+
+    let size = number(matches, "size")?;
+    if let Some(size) = size.filter(|size| *size <= 0.0) {
+        return Err(format!("--size must be above 0, got {}", size));
+    }
+    // Platform ayarında --size verilmezse platformun sınırı kullanılır
+    let target_size = match (size, &preset) {
+        (Some(size), _) => size,
+        (None, Some(_)) => 0.0,
+        (None, None) => settings.target_size,
+    };
+
+    let codec: Option<VideoCodec> = choice(matches, "codec")?;
+    let audio_codec: Option<AudioCodec> = choice(matches, "audio-codec")?;
+    let strip_audio = flag(matches, "strip-audio");
+
+    let mut request = ConversionRequest {
+        input: String::new(),
+        target_size,
+        start_time: number(matches, "start")?,
+        end_time: number(matches, "end")?,
+        ranges: ranges(matches)?,
+        clips: Vec::new(),
+        crop: None,
+        codec: codec.unwrap_or(settings.codec),
+        audio_codec: audio_codec.or(settings.audio_codec),
+        strip_audio: strip_audio || settings.strip_audio,
+        output_dir: string(matches, "out"),
+        output: OutputPolicy {
+            template: string(matches, "template").unwrap_or_else(|| settings.output.template.clone()),
+            collision: choice(matches, "on-exists")?.unwrap_or(settings.output.collision),
+            ..settings.output.clone()
+        },
+        preset: None,
+        animation: choice(matches, "animation")?,
+        sticker: flag(matches, "sticker"),
+        audio_only: choice(matches, "audio-only")?,
+        audio_stream: audio_stream(matches)?,
+    };
+    if let Some(preset) = preset {
+        preset.apply(&mut request);
+    }
+    Ok(request)
+}
+
+/// Paths are used as they are, anything with glob characters is expanded.
+/// Windows shells don't expand globs themselves.
+fn expand_inputs(patterns: &[String]) -> Vec<String> {
+    let mut inputs = Vec::new();
+
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            if Path::new(pattern).is_file() {
+                inputs.push(pattern.clone());
+            } else {
+                eprintln!("warning: {} not found, skipped", pattern);
+            }
+            continue;
+        }
+
+        match glob::glob(pattern) {
+            Ok(paths) => inputs.extend(
+                paths
+                    .flatten()
+                    .filter(|path| path.is_file())
+                    .map(|path| path.to_string_lossy().to_string()),
+            ),
+            Err(e) => eprintln!("warning: invalid pattern {}: {}", pattern, e),
+        }
+    }
+
+    inputs
+}
+
+fn flag(matches: &Matches, name: &str) -> bool {
+    matches!(
+        matches.args.get(name).map(|arg| &arg.value),
+        Some(Value::Bool(true))
+    )
+}
+
+fn strings(matches: &Matches, name: &str) -> Vec<String> {
+    match matches.args.get(name).map(|arg| &arg.value) {
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(value)) => vec![value.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn string(matches: &Matches, name: &str) -> Option<String> {
+    match matches.args.get(name).map(|arg| &arg.value) {
+        Some(Value::String(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn number(matches: &Matches, name: &str) -> Result<Option<f32>, String> {
+    match string(matches, name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("--{} expects a number, got {}", name, value)),
+        None => Ok(None),
+    }
+}
+
+/// `--audio-stream 2` is the second audio stream, the request counts from 0
+fn audio_stream(matches: &Matches) -> Result<u32, String> {
+    match string(matches, "audio-stream") {
+        Some(value) => match value.parse::<u32>() {
+            Ok(stream) if stream >= 1 => Ok(stream - 1),
+            _ => Err(format!("--audio-stream expects a number from 1, got {}", value)),
+        },
+        None => Ok(0),
+    }
+}
+
+/// `--keep 10-20 --keep 45-60`, parts of the video to keep and join
+fn ranges(matches: &Matches) -> Result<Vec<TimeRange>, String> {
+    strings(matches, "keep")
+        .iter()
+        .map(|value| {
+            let range = value.split_once('-').and_then(|(start, end)| {
+                Some(TimeRange {
+                    start: start.trim().parse().ok()?,
+                    end: end.trim().parse().ok()?,
+                })
+            });
+            match range {
+                Some(range) if range.end > range.start => Ok(range),
+                _ => Err(format!("--keep expects start-end in seconds, got {}", value)),
+            }
+        })
+        .collect()
+}
+
+/// Codec names are parsed with the same serde names the frontend sends
+fn choice<T: DeserializeOwned>(matches: &Matches, name: &str) -> Result<Option<T>, String> {
+    match string(matches, name) {
+        Some(value) => serde_json::from_value(Value::String(value.clone()))
+            .map(Some)
+            .map_err(|_| format!("unknown --{} {}", name, value)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ffmpeg::{run, sidecar, ConversionError};
+
+/// Video codecs the user can pick from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Vp9,
+    Av1,
+}
+
+impl Default for VideoCodec {
+    fn default() -> Self {
+        VideoCodec::H264
+    }
+}
+
+/// Output container, follows from the codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    Mp4,
+    Webm,
+}
+
+impl Container {
+    /// Name for ffmpeg's `-f`
+    pub fn format(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Webm => "webm",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.format()
+    }
+
+    /// Audio codec the container plays best with
+    pub fn default_audio_codec(&self) -> AudioCodec {
+        match self {
+            Container::Mp4 => AudioCodec::Aac,
+            Container::Webm => AudioCodec::Opus,
+        }
+    }
+
+    /// WebM only allows Opus (and Vorbis), MP4 takes all three
+    pub fn supports_audio(&self, codec: AudioCodec) -> bool {
+        match self {
+            Container::Mp4 => true,
+            Container::Webm => codec == AudioCodec::Opus,
+        }
+    }
+}
+
+/// Audio codecs the output can use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+    Mp3,
+}
+
+impl AudioCodec {
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "libopus",
+            AudioCodec::Mp3 => "libmp3lame",
+        }
+    }
+
+    /// Codec of a probed audio stream, `None` for codecs the output can't carry as they are
+    pub fn from_stream(codec_name: &str) -> Option<AudioCodec> {
+        match codec_name {
+            "aac" => Some(AudioCodec::Aac),
+            "opus" => Some(AudioCodec::Opus),
+            "mp3" => Some(AudioCodec::Mp3),
+            _ => None,
+        }
+    }
+
+    /// Lowest bit rate (kib/s) that still sounds acceptable for stereo speech and music
+    pub fn min_bitrate(&self) -> f32 {
+        match self {
+            AudioCodec::Aac => 32.0,
+            AudioCodec::Opus => 24.0,
+            AudioCodec::Mp3 => 48.0,
+        }
+    }
+
+    /// Above this (kib/s) more bits are not audible anymore
+    pub fn max_bitrate(&self) -> f32 {
+        match self {
+            AudioCodec::Aac => 160.0,
+            AudioCodec::Opus => 128.0,
+            AudioCodec::Mp3 => 192.0,
+        }
+    }
+
+    /// Below this bit rate (kib/s) stereo costs more than it's worth, the output is mono
+    pub fn mono_below(&self) -> f32 {
+        match self {
+            AudioCodec::Aac => 48.0,
+            AudioCodec::Opus => 32.0,
+            AudioCodec::Mp3 => 64.0,
+        }
+    }
+}
+
+impl VideoCodec {
+    pub const ALL: [VideoCodec; 4] = [
+        VideoCodec::H264,
+        VideoCodec::H265,
+        VideoCodec::Vp9,
+        VideoCodec::Av1,
+    ];
+
+    /// Same as the serialized name, used in file names
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::H265 => "h265",
+            VideoCodec::Vp9 => "vp9",
+            VideoCodec::Av1 => "av1",
+        }
+    }
+
+    /// Codec of a probed video stream, ffprobe's names differ from ours for H.265
+    pub fn from_stream(codec_name: &str) -> Option<VideoCodec> {
+        match codec_name {
+            "h264" => Some(VideoCodec::H264),
+            "hevc" => Some(VideoCodec::H265),
+            "vp9" => Some(VideoCodec::Vp9),
+            "av1" => Some(VideoCodec::Av1),
+            _ => None,
+        }
+    }
+
+    /// ffmpeg encoders for this codec, the preferred one first
+    fn encoders(&self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::H264 => &["libx264"],
+            VideoCodec::H265 => &["libx265"],
+            VideoCodec::Vp9 => &["libvpx-vp9"],
+            // SVT-AV1 libaom'dan çok daha hızlı, varsa onu kullan (tek geçiş)
+            VideoCodec::Av1 => &["libsvtav1", "libaom-av1"],
+        }
+    }
+
+    pub fn container(&self) -> Container {
+        match self {
+            VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Av1 => Container::Mp4,
+            VideoCodec::Vp9 => Container::Webm,
+        }
+    }
+
+    /// Bits needed for the same quality, relative to H.264
+    pub fn efficiency(&self) -> f64 {
+        match self {
+            VideoCodec::H264 => 1.0,
+            VideoCodec::H265 => 0.6,
+            VideoCodec::Vp9 => 0.65,
+            VideoCodec::Av1 => 0.5,
+        }
+    }
+
+    /// First encoder of this codec the bundled ffmpeg was built with
+    pub fn find_encoder(&self, available: &[String]) -> Option<&'static str> {
+        self.encoders()
+            .iter()
+            .copied()
+            .find(|encoder| available.iter().any(|a| a == encoder))
+    }
+}
+
+/// Names of all video encoders the bundled ffmpeg supports
+pub fn get_video_encoders() -> Result<Vec<String>, ConversionError> {
+    let output = run(
+        "ffmpeg",
+        sidecar("ffmpeg")?.args(["-hide_banner", "-encoders"]),
+    )?;
+
+    // Satır formatı: " V....D libx264              libx264 H.264 / AVC ..."
+    let encoders = output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(flags), Some(name)) if flags.len() == 6 && flags.starts_with('V') => {
+                    Some(name.to_string())
+                }
+                _ => None,
+            }
+        })
+        .collect();
+
+    Ok(encoders)
+}
+
+/// Codecs the user can actually pick with the bundled ffmpeg
+pub fn get_available_codecs() -> Result<Vec<VideoCodec>, ConversionError> {
+    let encoders = get_video_encoders()?;
+    Ok(VideoCodec::ALL
+        .iter()
+        .copied()
+        .filter(|codec| codec.find_encoder(&encoders).is_some())
+        .collect())
+}
+
+/// ffmpeg's libsvtav1 wrapper writes no first pass stats, it runs only the second pass
+pub fn is_two_pass(encoder: &str) -> bool {
+    encoder != "libsvtav1"
+}
+
+/// Encoder name and the two-pass flags each encoder needs, see `is_two_pass`.
+/// `passlog_prefix` is where the first pass writes its stats, it must be unique per job.
+pub fn video_args(encoder: &str, pass: u8, passlog_prefix: &str) -> Vec<String> {
+    let mut args = vec!["-c:v".to_string(), encoder.to_string()];
+
+    match encoder {
+        // Tek geçiş, bit hızı VBR ile tutturulur
+        "libsvtav1" => {
+            args.extend(vec![
+                "-preset".to_string(),
+                "8".to_string(),
+                "-svtav1-params".to_string(),
+                "rc=1".to_string(),
+            ]);
+            return args;
+        }
+        // x265 -pass seçeneğini tanımıyor, istatistik dosyası x265-params ile verilir
+        "libx265" => {
+            args.push("-x265-params".to_string());
+            args.push(format!(
+                "pass={}:stats={}",
+                pass,
+                escape_x265_path(&format!("{}.log", passlog_prefix))
+            ));
+            if pass == 2 {
+                // Apple cihazlarda oynatılabilmesi için
+                args.push("-tag:v".to_string());
+                args.push("hvc1".to_string());
+            }
+            return args;
+        }
+        "libvpx-vp9" => args.extend(vec![
+            "-row-mt".to_string(),
+            "1".to_string(),
+            "-deadline".to_string(),
+            "good".to_string(),
+            // İlk geçişte kalite önemli değil, hızlı geç
+            "-cpu-used".to_string(),
+            if pass == 1 { "4" } else { "2" }.to_string(),
+        ]),
+        "libaom-av1" => args.extend(vec![
+            "-row-mt".to_string(),
+            "1".to_string(),
+            "-cpu-used".to_string(),
+            "6".to_string(),
+        ]),
+        _ => {}
+    }
+
+    args.extend(vec![
+        "-passlogfile".to_string(),
+        passlog_prefix.to_string(),
+        "-pass".to_string(),
+        pass.to_string(),
+    ]);
+
+    args
+}
+
+/// x265-params splits on ':' so the drive letter of windows paths has to be escaped
+fn escape_x265_path(path: &str) -> String {
+    path.replace('\\', "/").replace(':', "\\:")
+}
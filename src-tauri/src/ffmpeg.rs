@@ -1,11 +1,19 @@
-use directories::{self, UserDirs};
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::env;
+use std::fmt;
 use std::fs::create_dir_all;
 use std::path::Path;
 use std::path::PathBuf;
-use std::env;
-use tauri::api::process::Command;
-use serde_json;
+use tauri::api::process::{Command, CommandEvent, Output};
+
+use crate::animation::AnimationFormat;
+use crate::codec::{is_two_pass, video_args, AudioCodec, Container, VideoCodec};
+use crate::jobs::JobHandle;
+use crate::output::OutputPolicy;
+use crate::presets::Preset;
+use crate::probe::{AudioStream, VideoStream};
 
 #[derive(Serialize, Deserialize)]
 /// file path is the full path inluding the video name, and output_dir is only the output dir
@@ -30,237 +38,932 @@ impl OutFile {
     }
 }
 
-fn remove_whitespace(s: &str) -> String {
-    s.chars().filter(|c| !c.is_whitespace()).collect()
+/// How many lines of ffmpeg's stderr are kept in an error
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Share of the progress bar each pass gets, the analysis pass is a lot faster than the real encode
+const PASS_WEIGHTS: [f32; 2] = [0.35, 0.65];
+
+/// How many times the encode runs at most while trying to hit the target size
+pub const MAX_SIZE_ATTEMPTS: u32 = 3;
+
+/// Outputs smaller than this share of the target waste quality and get re-encoded once
+const UNDERSHOOT_RATIO: f64 = 0.85;
+
+/// Retries aim a little below the target, rate control never lands exactly on it
+const RETRY_SAFETY: f64 = 0.97;
+
+/// Stream copies and single pass encodes fill the whole progress bar with one pass
+const SINGLE_PASS_WEIGHTS: [f32; 1] = [1.0];
+
+/// Share of the total bit rate the audio gets, before the codec's floor and ceiling
+const AUDIO_SHARE: f32 = 0.10;
+
+/// Everything that can go wrong while probing or converting a video.
+/// Serialized as `{ kind, message, stderr }` so the frontend can render it.
+#[derive(Debug, Clone)]
+pub enum ConversionError {
+    /// the ffmpeg/ffprobe sidecar could not be found or started
+    Sidecar { name: String, message: String },
+    /// ffprobe exited with an error, usually an unreadable or non-media input
+    ProbeFailed { input: String, stderr: String },
+    /// ffprobe returned a duration that is not a positive number
+    InvalidDuration { value: String },
+    /// ffmpeg exited with a non-zero code
+    FfmpegFailed { pass: u8, code: Option<i32>, stderr: String },
+    /// ffmpeg finished but the output file does not exist
+    OutputMissing { path: String },
+    /// ffmpeg finished but the output file is 0 bytes
+    OutputEmpty { path: String },
+    /// the audio and the lowest usable video bit rate need more than the requested size
+    TargetTooSmall { min_size: f32, target_size: f32 },
+    /// every attempt produced a file bigger than the requested size
+    OutputTooLarge { size: u64, target: u64, attempts: u32 },
+    /// the bundled ffmpeg was built without an encoder for the codec
+    CodecUnavailable { codec: VideoCodec },
+    /// the bundled ffmpeg was built without an encoder an export needs
+    EncoderUnavailable { encoder: String },
+    /// the input path has no file name
+    InvalidInput { input: String },
+    /// a preset is unknown or a preset file could not be read
+    InvalidPreset { message: String },
+    /// the selected audio stream does not exist, `count` is how many the input has
+    AudioStreamMissing { stream: u32, count: usize },
+    /// the finished sticker breaks the platform rules, one entry per broken rule
+    InvalidSticker { problems: Vec<String> },
+    /// the output file already exists and the collision policy is to skip
+    OutputExists { path: String },
+    /// a file system operation failed
+    Io { path: String, message: String },
+    /// the conversion thread stopped unexpectedly
+    Internal { message: String },
+    /// the user cancelled the conversion
+    Cancelled,
 }
 
-// copy ffmpeg-adsf to ffmpeg
-pub fn get_duration(input: &str) -> f32 {
-    let output = Command::new_sidecar("ffprobe")
-        .expect("failed to find ffprobe sidecar")
-        .args([
-            "-v",
-            "error",
-            "-show_entries",
-            "format=duration",
-            "-of",
-            "csv=p=0",
-            input,
-        ])
-        // TODO: write custom error handler
-        .output()
-        .expect("Failed to run ffprobe to get duration")
-        .stdout;
-
-    let duration = remove_whitespace(&output);
-
-    let parsed: f32 = duration.parse().unwrap();
+impl ConversionError {
+    /// Machine readable name of the error, used by the frontend
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConversionError::Sidecar { .. } => "sidecar",
+            ConversionError::ProbeFailed { .. } => "probe_failed",
+            ConversionError::InvalidDuration { .. } => "invalid_duration",
+            ConversionError::FfmpegFailed { .. } => "ffmpeg_failed",
+            ConversionError::OutputMissing { .. } => "output_missing",
+            ConversionError::OutputEmpty { .. } => "output_empty",
+            ConversionError::TargetTooSmall { .. } => "target_too_small",
+            ConversionError::OutputTooLarge { .. } => "output_too_large",
+            ConversionError::CodecUnavailable { .. } => "codec_unavailable",
+            ConversionError::EncoderUnavailable { .. } => "encoder_unavailable",
+            ConversionError::InvalidInput { .. } => "invalid_input",
+            ConversionError::InvalidPreset { .. } => "invalid_preset",
+            ConversionError::AudioStreamMissing { .. } => "audio_stream_missing",
+            ConversionError::InvalidSticker { .. } => "invalid_sticker",
+            ConversionError::OutputExists { .. } => "output_exists",
+            ConversionError::Io { .. } => "io",
+            ConversionError::Internal { .. } => "internal",
+            ConversionError::Cancelled => "cancelled",
+        }
+    }
 
-    parsed
+    /// The tail of ffmpeg/ffprobe's stderr, if the error came from one of them
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            ConversionError::ProbeFailed { stderr, .. }
+            | ConversionError::FfmpegFailed { stderr, .. } => Some(stderr),
+            _ => None,
+        }
+    }
 }
 
-/// Returns in kb
-pub fn get_original_audio_rate(input: &str) -> f32 {
-    let out = Command::new_sidecar("ffprobe")
-        .expect("failed to find ffprobe sidecar")
-        .args([
-            "-v",
-            "error",
-            "-select_streams",
-            "a:0",
-            "-show_entries",
-            "stream=bit_rate",
-            "-of",
-            "csv=p=0",
-            input,
-        ])
-        .output()
-        .expect("Failed to run ffprobe to get original audio rate");
-
-    let output = out.stdout;
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::Sidecar { name, message } => {
+                write!(f, "Failed to run {}: {}", name, message)
+            }
+            ConversionError::ProbeFailed { input, .. } => {
+                write!(f, "Could not read video information from {}", input)
+            }
+            ConversionError::InvalidDuration { value } => {
+                write!(f, "Video duration could not be read (got \"{}\")", value)
+            }
+            ConversionError::FfmpegFailed { pass, code, .. } => match code {
+                Some(code) => write!(f, "ffmpeg pass {} failed with exit code {}", pass, code),
+                None => write!(f, "ffmpeg pass {} was terminated", pass),
+            },
+            ConversionError::OutputMissing { path } => {
+                write!(f, "Output file was not created: {}", path)
+            }
+            ConversionError::OutputEmpty { path } => write!(f, "Output file is empty: {}", path),
+            ConversionError::TargetTooSmall {
+                min_size,
+                target_size,
+            } => write!(
+                f,
+                "Video can not be compressed to {} MB, it needs at least {:.2} MB",
+                target_size, min_size
+            ),
+            ConversionError::OutputTooLarge {
+                size,
+                target,
+                attempts,
+            } => write!(
+                f,
+                "Output is still {:.2} MB after {} attempts, the target is {:.2} MB",
+                *size as f64 / 1_000_000.0,
+                attempts,
+                *target as f64 / 1_000_000.0
+            ),
+            ConversionError::CodecUnavailable { codec } => {
+                write!(f, "The bundled ffmpeg has no encoder for {:?}", codec)
+            }
+            ConversionError::EncoderUnavailable { encoder } => {
+                write!(f, "The bundled ffmpeg was built without {}", encoder)
+            }
+            ConversionError::InvalidInput { input } => write!(f, "Invalid input file: {}", input),
+            ConversionError::InvalidPreset { message } => write!(f, "Invalid preset: {}", message),
+            ConversionError::AudioStreamMissing { stream, count } => match count {
+                0 => write!(f, "The input has no audio"),
+                _ => write!(
+                    f,
+                    "Audio stream {} does not exist, the input has {}",
+                    stream + 1,
+                    count
+                ),
+            },
+            ConversionError::InvalidSticker { problems } => {
+                write!(f, "Sticker does not meet the rules: {}", problems.join("; "))
+            }
+            ConversionError::OutputExists { path } => {
+                write!(f, "Output file already exists, skipped: {}", path)
+            }
+            ConversionError::Io { path, message } => write!(f, "{}: {}", path, message),
+            ConversionError::Internal { message } => {
+                write!(f, "Conversion stopped unexpectedly: {}", message)
+            }
+            ConversionError::Cancelled => write!(f, "Conversion was cancelled"),
+        }
+    }
+}
 
-    let arate = remove_whitespace(&output);
+impl std::error::Error for ConversionError {}
 
-    if arate == "N/A" {
-        return 0.00;
+impl Serialize for ConversionError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ConversionError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("stderr", &self.stderr())?;
+        state.end()
     }
+}
 
-    println!("arate {}", arate);
+/// Keeps the last few non-empty lines of ffmpeg's stderr, that's where the actual error is
+pub fn stderr_tail(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    let start = lines.len().saturating_sub(STDERR_TAIL_LINES);
+    lines[start..].join("\n")
+}
 
-    let parsed: f32 = arate
-        .parse::<f32>()
-        .expect("Failed to parse original audio rate")
-        / 1024.00;
+pub(crate) fn sidecar(name: &str) -> Result<Command, ConversionError> {
+    Command::new_sidecar(name).map_err(|e| ConversionError::Sidecar {
+        name: name.to_string(),
+        message: e.to_string(),
+    })
+}
 
-    println!("arate: {}", arate);
+pub(crate) fn run(name: &str, command: Command) -> Result<Output, ConversionError> {
+    command.output().map_err(|e| ConversionError::Sidecar {
+        name: name.to_string(),
+        message: e.to_string(),
+    })
+}
 
-    parsed
-    // use 7.8
+/// File system error of `path`, the one way every module builds `ConversionError::Io`
+pub(crate) fn io_error(path: &Path, e: std::io::Error) -> ConversionError {
+    ConversionError::Io {
+        path: path.to_string_lossy().to_string(),
+        message: e.to_string(),
+    }
 }
 
 pub fn get_target_size(audio_rate: f32, duration: f32) -> f32 {
-    let size = (audio_rate * duration) / 8192.00;
-    size
+    (audio_rate * duration) / 8192.00
 }
 
 pub fn is_minsize(min_size: f32, size: f32) -> bool {
-    return min_size < size;
+    min_size < size
+}
+
+/// Bit rate of the whole output (video and audio) that fits the size, in kib/s
+pub fn get_total_rate(size: f32, duration: f32) -> f32 {
+    (size * 8192.00) / (1.048576 * duration)
 }
 
 /// returns in kib/s
 pub fn get_target_video_rate(size: f32, duration: f32, audio_rate: f32) -> f32 {
-    let size = (size * 8192.00) / (1.048576 * duration) - audio_rate;
-    size
+    get_total_rate(size, duration) - audio_rate
 }
 
-pub fn convert_first(input: &str, video_bitrate: f32, start_time: Option<f32>, end_time: Option<f32>) {
-    let temp_dir = env::temp_dir();
-    let nul = if env::consts::OS == "windows" {
-        "nul"
+/// How the audio track of the output is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioSettings {
+    pub codec: AudioCodec,
+    /// kib/s
+    pub bitrate: f32,
+    /// Hz
+    pub sample_rate: u32,
+    pub channels: u32,
+}
+
+impl AudioSettings {
+    pub fn args(&self) -> Vec<String> {
+        vec![
+            "-c:a".to_string(),
+            self.codec.encoder().to_string(),
+            "-b:a".to_string(),
+            format!("{}k", self.bitrate),
+            "-ar".to_string(),
+            self.sample_rate.to_string(),
+            "-ac".to_string(),
+            self.channels.to_string(),
+        ]
+    }
+}
+
+/// Picks the audio codec, bit rate, sample rate and channels from the total bit rate budget.
+/// Audio gets `AUDIO_SHARE` of the budget, kept between the codec's floor and ceiling
+/// and never above the source, so a 320 kbps track doesn't eat the video's bits.
+pub fn get_audio_settings(
+    total_rate: f32,
+    container: Container,
+    requested: Option<AudioCodec>,
+    source: Option<&AudioStream>,
+) -> AudioSettings {
+    let share = total_rate * AUDIO_SHARE;
+
+    // Konteynerin desteklemediği codec seçildiyse varsayılana dön. Dar bütçede bile MP4'e
+    // Opus konmaz, Safari ve iOS oynatmıyor. AAC tabanında kalır ve mono'ya indirilir
+    let codec = requested
+        .filter(|codec| container.supports_audio(*codec))
+        .unwrap_or_else(|| container.default_audio_codec());
+
+    let mut bitrate = share.clamp(codec.min_bitrate(), codec.max_bitrate());
+    // Kaynaktan yüksek bit hızı kaliteyi artırmaz, sadece yer kaplar
+    if let Some(source_rate) = source.and_then(|audio| audio.bit_rate) {
+        bitrate = bitrate.min((source_rate as f32 / 1024.0).max(codec.min_bitrate()));
+    }
+
+    get_audio_format(codec, bitrate.round(), source)
+}
+
+/// Channels and sample rate for a bit rate (kib/s), the source is never upmixed or upsampled
+fn get_audio_format(codec: AudioCodec, bitrate: f32, source: Option<&AudioStream>) -> AudioSettings {
+    // 5.1 ve üzeri her zaman stereo'ya, düşük bit hızında mono'ya indirilir
+    let source_channels = source.and_then(|audio| audio.channels).unwrap_or(2);
+    let channels = if bitrate < codec.mono_below() {
+        1
     } else {
-        "/dev/null"
+        source_channels.clamp(1, 2)
     };
 
-    // Formatlı stringleri önceden oluştur (ömür sorunlarını önlemek için)
-    let bitrate_str = format!("{}k", video_bitrate);
-    let passlog_str = temp_dir.to_str().expect("Failed to convert temp dir to string");
+    let sample_rate = match codec {
+        // libopus sadece 48 kHz ve alt katlarını kabul eder
+        AudioCodec::Opus => 48000,
+        _ => {
+            let rate = if bitrate < 40.0 {
+                22050
+            } else if bitrate < 64.0 {
+                32000
+            } else {
+                44100
+            };
+            // Kaynak örnekleme hızı yükseltilmez
+            match source.and_then(|audio| audio.sample_rate) {
+                Some(source_rate) if source_rate < rate => source_rate,
+                _ => rate,
+            }
+        }
+    };
 
-    // İki aşamalı encoding yaparken ilk aşamada da zaman parametrelerini doğru sırada uygulamalıyız
-    // Bu, kesme + crop kombinasyonunda 0 byte video oluşma sorununu çözer
+    AudioSettings {
+        codec,
+        bitrate,
+        sample_rate,
+        channels,
+    }
+}
 
-    // Temel parametreler
-    let mut args = vec!["-y".to_string()];
+/// Target size in MB to bytes, the same unit `get_target_video_rate` aims for
+pub fn get_target_bytes(size: f32) -> u64 {
+    (size as f64 * 1_000_000.0) as u64
+}
 
-    // Start time kesinlikle input'tan önce gelmeli
-    if let Some(start) = start_time {
-        if start > 0.0 {
-            args.push("-ss".to_string());
-            args.push(start.to_string());
+/// Scales the video bit rate by how far the last output missed the target.
+/// Audio takes the same space in every attempt so only the video part is scaled.
+pub fn get_corrected_video_rate(
+    video_rate: f32,
+    audio_rate: f32,
+    duration: f32,
+    output_bytes: u64,
+    target_bytes: u64,
+) -> f32 {
+    let audio_bytes = audio_rate as f64 * 1024.0 / 8.0 * duration as f64;
+    let video_bytes = (output_bytes as f64 - audio_bytes).max(1.0);
+    let wanted_bytes = target_bytes as f64 * RETRY_SAFETY - audio_bytes;
+
+    (video_rate as f64 * wanted_bytes / video_bytes) as f32
+}
+
+/// Length in seconds of the part of the video that actually gets encoded
+pub fn get_clip_duration(duration: f32, start_time: Option<f32>, end_time: Option<f32>) -> f32 {
+    let start = start_time.unwrap_or(0.0).max(0.0);
+    let end = end_time
+        .filter(|end| *end > start)
+        .unwrap_or(duration)
+        .min(duration);
+
+    (end - start).max(0.0)
+}
+
+/// Part of the video to keep, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl TimeRange {
+    pub fn duration(&self) -> f32 {
+        self.end - self.start
+    }
+}
+
+/// Ranges inside the video, sorted, with overlapping ones merged and empty ones dropped
+pub fn get_kept_ranges(duration: f32, ranges: &[TimeRange]) -> Vec<TimeRange> {
+    let mut ranges: Vec<TimeRange> = ranges
+        .iter()
+        .map(|range| TimeRange {
+            start: range.start.max(0.0),
+            end: range.end.min(duration),
+        })
+        .filter(|range| range.duration() > 0.0)
+        .collect();
+    ranges.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(Ordering::Equal));
+
+    let mut kept: Vec<TimeRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match kept.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => kept.push(range),
         }
     }
+    kept
+}
 
-    // Input dosyası
-    args.push("-i".to_string());
-    args.push(input.to_string());
+/// Drops and shortens ranges from the end until they add up to at most `max_duration`
+pub fn limit_ranges(ranges: &[TimeRange], max_duration: f32) -> Vec<TimeRange> {
+    let mut left = max_duration;
+    let mut limited = Vec::new();
 
-    // End time input'tan sonra gelmeli
-    if let Some(end) = end_time {
-        // Video süresini hesapla
-        if let Some(start) = start_time {
-            if end > start {
-                // Başlangıç ve bitiş varsa: süre = bitiş - başlangıç
-                let duration = end - start;
-                args.push("-t".to_string());  // -to yerine -t kullan (süre)
-                args.push(duration.to_string());
-            } else {
-                // Bitiş başlangıçtan küçükse hatalı durum - -to ile devam et
-                args.push("-to".to_string());
-                args.push(end.to_string());
-            }
-        } else {
-            // Sadece bitiş zamanı varsa -to kullan
-            args.push("-to".to_string());
-            args.push(end.to_string());
-        }
-    }
-
-    // Diğer parameterler
-    args.extend(vec![
-        "-c:v".to_string(),
-        "libx264".to_string(),
-        "-passlogfile".to_string(),
-        passlog_str.to_string(),
-        "-filter:v".to_string(),
-        "scale=1280:-2".to_string(),
-        "-b:v".to_string(),
-        bitrate_str,
-        "-pass".to_string(),
-        "1".to_string(),
-        "-an".to_string(),           // Ses yok
-        "-f".to_string(),
-        "mp4".to_string(),
-        "-pix_fmt".to_string(),
-        "yuv420p".to_string(),
-        nul.to_string()
-    ]);
-
-    // Debug için komutu yazdır
-    let cmd_str = args.join(" ");
-    println!("İlk geçiş FFmpeg komutu: {}", cmd_str);
-
-    // Hata yakalama ile çalıştır
-    match Command::new_sidecar("ffmpeg")
-        .expect("failed to get ffmpeg sidecar")
-        .args(args)
-        .output() {
-            Ok(cmd_output) => {
-                println!("İlk geçiş tamamlandı");
-
-                // Hata çıktısını kontrol et
-                let stderr_str = String::from_utf8_lossy(cmd_output.stderr.as_bytes());
-                if !stderr_str.is_empty() {
-                    println!("İlk geçiş FFmpeg stderr:");
-                    println!("------- İLK GEÇİŞ STDERR BAŞLANGICI -------");
-                    println!("{}", stderr_str);
-                    println!("------- İLK GEÇİŞ STDERR SONU -------");
-                }
-            },
-            Err(e) => {
-                println!("FFmpeg ilk geçiş hatası: {}", e);
-                panic!("FFmpeg ilk geçiş hatası: {}", e);
-            }
-        };
+    for range in ranges {
+        if left <= 0.0 {
+            break;
+        }
+        limited.push(TimeRange {
+            start: range.start,
+            end: range.end.min(range.start + left),
+        });
+        left -= range.duration();
+    }
+    limited
+}
+
+/// One input of a merge with its own trim
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clip {
+    pub input: String,
+    #[serde(default)]
+    pub start_time: Option<f32>,
+    #[serde(default)]
+    pub end_time: Option<f32>,
 }
 
-pub fn convert_out(
-    input: &str,
-    video_bitrate: f32,
-    audio_bitrate: f32,
-    output: &str,
-    start_time: Option<f32>,
-    end_time: Option<f32>,
-    crop: Option<serde_json::Value>
-) {
-    let temp_dir = env::temp_dir();
+/// A clip of a merge after probing, what the encode needs to read and normalize it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeClip {
+    pub input: String,
+    /// seconds
+    pub start: f32,
+    /// seconds, after trimming
+    pub duration: f32,
+    /// inputs without audio get silence so `concat` lines up
+    pub audio: bool,
+}
+
+/// Everything the user picked for a single conversion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionRequest {
+    pub input: String,
+    /// MB
+    pub target_size: f32,
+    #[serde(default)]
+    pub start_time: Option<f32>,
+    #[serde(default)]
+    pub end_time: Option<f32>,
+    /// parts to keep and join, replaces `start_time` and `end_time` when not empty
+    #[serde(default)]
+    pub ranges: Vec<TimeRange>,
+    /// inputs to join into one output in this order, the output is named after `input`.
+    /// Replaces the trim, ranges and crop when not empty
+    #[serde(default)]
+    pub clips: Vec<Clip>,
+    #[serde(default)]
+    pub crop: Option<Crop>,
+    #[serde(default)]
+    pub codec: VideoCodec,
+    /// `None` lets `get_audio_settings` pick one for the budget
+    #[serde(default)]
+    pub audio_codec: Option<AudioCodec>,
+    /// drop the audio track and give the whole budget to the video
+    #[serde(default)]
+    pub strip_audio: bool,
+    /// folder picked for this conversion, overrides the location of the output policy
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub output: OutputPolicy,
+    /// platform limits, already applied to the size, codec and audio fields by `Preset::apply`
+    #[serde(default)]
+    pub preset: Option<Preset>,
+    /// export a GIF or animated WebP instead of a video, only the trim and crop are used
+    #[serde(default)]
+    pub animation: Option<AnimationFormat>,
+    /// export a Telegram video sticker, only the trim and crop are used
+    #[serde(default)]
+    pub sticker: bool,
+    /// export only the audio in this format, only the trim and `audio_stream` are used
+    #[serde(default)]
+    pub audio_only: Option<AudioFormat>,
+    /// which audio stream to keep, 0 is the first one
+    #[serde(default)]
+    pub audio_stream: u32,
+}
+
+/// Crop selection from the editor, in percent of the displayed frame
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Crop {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Crop in pixels of the frame the filter chain sees, all values even
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelCrop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelCrop {
+    pub fn filter(&self) -> String {
+        // FFmpeg crop formatı: crop=width:height:x:y
+        format!("crop={}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
 
-    // Create a PathBuf from the output string
-    let output_path = PathBuf::from(output);
+/// Smallest crop that is still worth encoding
+const MIN_CROP_SIZE: u32 = 16;
 
-    // Get the parent directory
-    let parent_dir = output_path.parent().unwrap();
+/// yuv420p needs even sizes and offsets
+fn even(value: f64) -> u32 {
+    (value.max(0.0) as u32) & !1
+}
 
-    // Create the parent directory if it doesn't exist
-    create_dir_all(parent_dir).unwrap();
+/// Converts the editor's crop percentages to pixels of the source video.
+/// ffmpeg rotates the frame before the filters run and non-square pixels are
+/// stretched first (see `geometry_filters`), so the display size is
+/// the frame the crop applies to. Returns `None` when the crop should be skipped.
+/// The editor's estimate calls this on every change, so nothing is logged here.
+pub fn get_pixel_crop(crop: &Crop, video: &VideoStream) -> Option<PixelCrop> {
+    // Crop sadece geçerli değerler için uygula
+    if !(crop.width > 0.0 && crop.height > 0.0 && crop.width < 100.0 && crop.height < 100.0) {
+        return None;
+    }
 
-    // Format değerlerini önceden oluştur
-    let abi = if audio_bitrate == 0.00 {
-        "copy".to_string()
+    // Kaynak video boyutu bilinmiyorsa crop atlanır
+    let (frame_width, frame_height) = video.display_size();
+    if frame_width == 0 || frame_height == 0 {
+        return None;
+    }
+
+    // Piksel değerlerine çevir - gerçek video boyutuna göre
+    let x = even(crop.x.clamp(0.0, 100.0) / 100.0 * frame_width as f64).min(frame_width);
+    let y = even(crop.y.clamp(0.0, 100.0) / 100.0 * frame_height as f64).min(frame_height);
+    let width = even(crop.width / 100.0 * frame_width as f64).min(even((frame_width - x) as f64));
+    let height = even(crop.height / 100.0 * frame_height as f64).min(even((frame_height - y) as f64));
+
+    // Boyut sınırlarını kontrol et - hata riskini azalt
+    if width < MIN_CROP_SIZE || height < MIN_CROP_SIZE {
+        return None;
+    }
+
+    Some(PixelCrop {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Size to stretch non-square pixels to before cropping, `None` for square pixels
+pub fn get_sar_scale(video: &VideoStream) -> Option<(u32, u32)> {
+    video.sample_aspect_ratio.as_ref()?;
+
+    let (width, height) = video.display_size();
+    Some((even(width as f64), even(height as f64)))
+}
+
+/// Output sizes to pick from, as the short side of the frame, best first
+const RESOLUTION_LADDER: [u32; 5] = [1080, 720, 540, 480, 360];
+
+/// Frame rate caps to try before dropping to a lower resolution
+const FPS_LADDER: [f32; 3] = [60.0, 30.0, 24.0];
+
+/// Below this many bits per pixel per frame x264 output turns visibly blocky
+const MIN_BITS_PER_PIXEL: f64 = 0.05;
+
+/// Bits per pixel the codec needs to look like H.264 at `MIN_BITS_PER_PIXEL`
+pub fn min_bits_per_pixel(codec: VideoCodec) -> f64 {
+    MIN_BITS_PER_PIXEL * codec.efficiency()
+}
+
+/// Resolution and frame rate the video gets encoded at
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct OutputFormat {
+    pub width: u32,
+    pub height: u32,
+    /// frame rate cap, `None` keeps the source frame rate
+    pub fps: Option<f32>,
+    pub bits_per_pixel: f64,
+}
+
+/// Picks the largest resolution and frame rate the bit rate budget can fill with
+/// at least `MIN_BITS_PER_PIXEL` (less for codecs more efficient than H.264).
+/// Lowering the frame rate is tried before dropping a resolution step and sources
+/// smaller than a step are never upscaled.
+/// `source` is the frame size after crop, `video_rate` is in kib/s,
+/// `max_short_side` is the platform limit of a preset.
+pub fn get_output_format(
+    video_rate: f32,
+    source: (u32, u32),
+    source_fps: Option<f32>,
+    codec: VideoCodec,
+    max_short_side: Option<u32>,
+) -> OutputFormat {
+    let (source_width, source_height) = source;
+    let short_side = source_width.min(source_height).max(2);
+    let source_fps = source_fps.filter(|fps| *fps > 0.0).unwrap_or(30.0);
+    let bits = video_rate as f64 * 1024.0;
+    let min_bits_per_pixel = min_bits_per_pixel(codec);
+
+    // Kaynaktan büyük basamaklar atlanır, kaynak basamaklar arasındaysa kendi boyutu da denenir
+    let top = short_side
+        .min(RESOLUTION_LADDER[0])
+        .min(max_short_side.unwrap_or(u32::MAX).max(2));
+    let mut heights = vec![top];
+    heights.extend(RESOLUTION_LADDER.iter().copied().filter(|h| *h < top));
+
+    let mut fps_caps: Vec<f32> = FPS_LADDER.iter().map(|cap| cap.min(source_fps)).collect();
+    fps_caps.dedup();
+
+    let mut best = None;
+    for height in heights.iter() {
+        for fps in fps_caps.iter() {
+            let (width, height) = scaled_size(source, *height);
+            let bits_per_pixel = bits / (width as f64 * height as f64 * *fps as f64);
+            let format = OutputFormat {
+                width,
+                height,
+                fps: if *fps < source_fps { Some(*fps) } else { None },
+                bits_per_pixel,
+            };
+
+            if bits_per_pixel >= min_bits_per_pixel {
+                return format;
+            }
+            best = Some(format);
+        }
+    }
+
+    // Hiçbiri yetmiyorsa en küçük boyut ve en düşük fps
+    best.unwrap_or(OutputFormat {
+        width: source_width & !1,
+        height: source_height & !1,
+        fps: None,
+        bits_per_pixel: 0.0,
+    })
+}
+
+/// Scales a frame so its short side is `short_side`, keeping the aspect ratio and even sizes
+fn scaled_size(source: (u32, u32), short_side: u32) -> (u32, u32) {
+    let (width, height) = (source.0.max(2) as f64, source.1.max(2) as f64);
+    let short_side = short_side as f64;
+
+    let (width, height) = if width <= height {
+        (short_side, height * short_side / width)
     } else {
-        format!("{}k", audio_bitrate)
+        (width * short_side / height, short_side)
     };
 
-    // Passlog ve bitrate değerlerini önceden oluştur
-    let bitrate_str = format!("{}k", video_bitrate);
-    let passlog_str = temp_dir.to_str().expect("Failed to convert temp dir to string").to_string();
+    (even(width.round()).max(2), even(height.round()).max(2))
+}
 
-    // İlk geçiş - video analizi için
-    println!("İlk geçiş başlatılıyor...");
-    convert_first(
-        input,
-        video_bitrate,
-        start_time,
-        end_time
-    );
+/// What a conversion is doing right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversionStage {
+    Probing,
+    Pass1,
+    Pass2,
+    /// checking the output against the target size
+    Verifying,
+    /// remuxing a source that already fits, nothing is encoded
+    Copying,
+    /// single pass encode of SVT-AV1, an animation or audio. `Pass1` and `Pass2`
+    /// are only reported by two-pass encodes
+    Encoding,
+    Done,
+}
 
-    println!("İkinci geçiş başlatılıyor...");
+impl Default for ConversionStage {
+    fn default() -> Self {
+        ConversionStage::Probing
+    }
+}
 
-    // Komut oluşturma
-    let mut args = Vec::new();
+/// Progress of a running conversion, emitted as the `conversion_progress` event
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConversionProgress {
+    pub stage: ConversionStage,
+    /// 0 before encoding starts, then 1 or 2
+    pub pass: u8,
+    /// starts at 1, increases every time the output missed the target size
+    pub attempt: u32,
+    /// overall progress in percent, both passes included
+    pub percent: f32,
+    /// progress of the current pass in percent
+    pub pass_percent: f32,
+    /// estimated seconds until the whole conversion is done
+    pub eta: Option<f32>,
+    /// current output bit rate in kbit/s
+    pub bitrate: Option<f32>,
+    /// encoding speed relative to playback, 2.0 means twice as fast
+    pub speed: Option<f32>,
+    pub fps: Option<f32>,
+    /// bytes written so far
+    pub total_size: Option<u64>,
+}
+
+impl ConversionProgress {
+    /// Start of a size attempt, before pass 1 reports anything
+    pub fn start() -> Self {
+        ConversionProgress {
+            stage: ConversionStage::Pass1,
+            pass: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn verifying() -> Self {
+        ConversionProgress {
+            stage: ConversionStage::Verifying,
+            pass: PASS_WEIGHTS.len() as u8,
+            percent: 100.0,
+            pass_percent: 100.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn done() -> Self {
+        ConversionProgress {
+            stage: ConversionStage::Done,
+            pass: PASS_WEIGHTS.len() as u8,
+            percent: 100.0,
+            pass_percent: 100.0,
+            eta: Some(0.0),
+            ..Default::default()
+        }
+    }
+}
+
+/// One block of the `key=value` lines ffmpeg writes with `-progress`.
+/// A block ends with a `progress=continue` or `progress=end` line.
+#[derive(Debug, Default)]
+struct ProgressBlock {
+    out_time_us: Option<i64>,
+    speed: Option<f32>,
+    fps: Option<f32>,
+    bitrate: Option<f32>,
+    total_size: Option<u64>,
+}
+
+impl ProgressBlock {
+    /// Returns true when the line closes the block
+    fn feed(&mut self, line: &str) -> bool {
+        let (key, value) = match line.trim().split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return false,
+        };
+
+        // Değer henüz bilinmiyorsa ffmpeg "N/A" yazar, parse başarısız olur ve None kalır
+        match key {
+            // out_time_ms isminin aksine mikrosaniyedir
+            "out_time_us" | "out_time_ms" => self.out_time_us = value.parse().ok(),
+            "speed" => self.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "fps" => self.fps = value.parse().ok(),
+            "bitrate" => self.bitrate = value.trim_end_matches("kbits/s").trim().parse().ok(),
+            "total_size" => self.total_size = value.parse().ok(),
+            "progress" => return true,
+            _ => {}
+        }
+
+        false
+    }
+}
+
+/// Turns the progress of each ffmpeg pass into overall progress reports
+pub struct ProgressReporter<'a> {
+    /// trimmed duration of the clip in seconds
+    duration: f32,
+    attempt: u32,
+    /// share of the progress bar of each pass, `SINGLE_PASS_WEIGHTS` for a single pass
+    weights: &'static [f32],
+    /// stage of a single pass, two-pass stages follow the pass number
+    single_pass: Option<ConversionStage>,
+    on_progress: Box<dyn FnMut(ConversionProgress) + Send + 'a>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub fn new(duration: f32, on_progress: impl FnMut(ConversionProgress) + Send + 'a) -> Self {
+        ProgressReporter {
+            duration,
+            attempt: 1,
+            weights: &PASS_WEIGHTS,
+            single_pass: None,
+            on_progress: Box::new(on_progress),
+        }
+    }
+
+    pub fn report(&mut self, mut progress: ConversionProgress) {
+        progress.attempt = self.attempt;
+        (self.on_progress)(progress);
+    }
+
+    /// Starts reporting a new size attempt, progress starts again from 0
+    pub fn start_attempt(&mut self, attempt: u32) {
+        self.attempt = attempt;
+        self.weights = &PASS_WEIGHTS;
+        self.single_pass = None;
+        self.report(ConversionProgress::start());
+    }
+
+    /// Starts reporting a single pass attempt, a stream copy or a single pass encode
+    pub fn start_single_pass(&mut self, stage: ConversionStage, attempt: u32) {
+        self.attempt = attempt;
+        self.weights = &SINGLE_PASS_WEIGHTS;
+        self.single_pass = Some(stage);
+        self.report(ConversionProgress {
+            stage,
+            pass: 1,
+            ..Default::default()
+        });
+    }
+
+    /// The length of the clip is only known after probing
+    pub fn set_duration(&mut self, duration: f32) {
+        self.duration = duration;
+    }
+
+    fn update(&mut self, pass: u8, block: &ProgressBlock) {
+        let weights = self.weights;
+        let index = (pass.max(1) as usize - 1).min(weights.len() - 1);
+        let out_time = block.out_time_us.unwrap_or(0).max(0) as f32 / 1_000_000.0;
+
+        let pass_fraction = if self.duration > 0.0 {
+            (out_time / self.duration).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let done_before: f32 = weights[..index].iter().sum();
+        let percent = (done_before + weights[index] * pass_fraction) * 100.0;
+
+        // Kalan süre: bu geçişin kalanı + sonraki geçişler (ağırlık oranıyla tahmin)
+        let eta = block.speed.filter(|speed| *speed > 0.0).map(|speed| {
+            let pass_time = self.duration / speed;
+            let later: f32 = weights[index + 1..].iter().sum();
+            pass_time * (1.0 - pass_fraction) + pass_time * later / weights[index]
+        });
+
+        let stage = match self.single_pass {
+            Some(stage) => stage,
+            None if pass == 1 => ConversionStage::Pass1,
+            None => ConversionStage::Pass2,
+        };
+
+        self.report(ConversionProgress {
+            stage,
+            pass,
+            attempt: self.attempt,
+            percent,
+            pass_percent: pass_fraction * 100.0,
+            eta,
+            bitrate: block.bitrate,
+            speed: block.speed,
+            fps: block.fps,
+            total_size: block.total_size,
+        });
+    }
+}
+
+/// Runs one ffmpeg pass, streaming its `-progress pipe:1` output to the reporter.
+/// The process is attached to the job so it can be killed or suspended from the frontend.
+pub fn run_pass(
+    pass: u8,
+    args: Vec<String>,
+    progress: &mut ProgressReporter,
+    job: &JobHandle,
+) -> Result<(), ConversionError> {
+    if job.is_cancelled() {
+        return Err(ConversionError::Cancelled);
+    }
+
+    let (mut rx, child) = sidecar("ffmpeg")?
+        .args(args)
+        .spawn()
+        .map_err(|e| ConversionError::Sidecar {
+            name: "ffmpeg".to_string(),
+            message: e.to_string(),
+        })?;
+
+    job.attach(child);
+
+    let mut block = ProgressBlock::default();
+    let mut stderr = String::new();
+    let mut code = None;
+
+    while let Some(event) = rx.blocking_recv() {
+        match event {
+            CommandEvent::Stdout(line) if block.feed(&line) => progress.update(pass, &block),
+            CommandEvent::Stderr(line) => {
+                stderr.push_str(&line);
+                stderr.push('\n');
+            }
+            CommandEvent::Terminated(payload) => code = payload.code,
+            CommandEvent::Error(e) => eprintln!("FFmpeg {}. geçiş okuma hatası: {}", pass, e),
+            _ => {}
+        }
+    }
+
+    job.detach();
+    eprintln!("{}. geçiş tamamlandı", pass);
+
+    // İptal edilen process sinyal ile sonlandığı için exit code'a bakmadan çık
+    if job.is_cancelled() {
+        return Err(ConversionError::Cancelled);
+    }
+
+    // Hata çıktısını göster (önemli)
+    if !stderr.is_empty() {
+        eprintln!("------- {}. GEÇİŞ STDERR BAŞLANGICI -------", pass);
+        eprintln!("{}", stderr);
+        eprintln!("------- {}. GEÇİŞ STDERR SONU -------", pass);
+    }
+
+    if code != Some(0) {
+        return Err(ConversionError::FfmpegFailed {
+            pass,
+            code,
+            stderr: stderr_tail(&stderr),
+        });
+    }
 
-    // 1. Global parametreler
-    args.push("-y".to_string()); // Var olan dosyanın üzerine yaz
+    Ok(())
+}
+
+/// `-ss` before the input so seeking is fast, the length after it
+pub fn input_args(input: &str, start_time: Option<f32>, end_time: Option<f32>) -> Vec<String> {
+    let mut args = Vec::new();
 
-    // 2. Input parametreleri - SEEK ÖNEMLİ: Önce -ss, sonra -i
+    // Start time kesinlikle input'tan önce gelmeli
     if let Some(start) = start_time {
         if start > 0.0 {
             args.push("-ss".to_string());
@@ -268,205 +971,1064 @@ pub fn convert_out(
         }
     }
 
-    // 3. Input belirt
+    // Input dosyası
     args.push("-i".to_string());
     args.push(input.to_string());
 
-    // 4. End time parametresi (input'tan SONRA)
+    // End time input'tan sonra gelmeli
     if let Some(end) = end_time {
-        // Video süresini hesapla
-        if let Some(start) = start_time {
-            if end > start {
-                // Başlangıç ve bitiş varsa: süre = bitiş - başlangıç
-                let duration = end - start;
-                args.push("-t".to_string());  // -to yerine -t kullan (süre)
-                args.push(duration.to_string());
-            } else {
-                // Bitiş başlangıçtan küçükse hatalı durum - -to ile devam et
+        match start_time {
+            // Başlangıç ve bitiş varsa: süre = bitiş - başlangıç
+            Some(start) if end > start => {
+                args.push("-t".to_string()); // -to yerine -t kullan (süre)
+                args.push((end - start).to_string());
+            }
+            // Sadece bitiş zamanı varsa veya bitiş başlangıçtan küçükse -to ile devam et
+            _ => {
                 args.push("-to".to_string());
                 args.push(end.to_string());
             }
-        } else {
-            // Sadece bitiş zamanı varsa -to kullan
-            args.push("-to".to_string());
-            args.push(end.to_string());
         }
     }
 
-    // 5. Video codec parametreleri
-    args.extend(vec![
-        "-c:v".to_string(),
-        "libx264".to_string(),
-        "-passlogfile".to_string(),
-        passlog_str.clone(),
-    ]);
+    args
+}
+
+/// Global arguments of every ffmpeg run: overwrite the output and write the
+/// progress to stdout, where `run_pass` reads it
+pub fn progress_args() -> Vec<String> {
+    vec![
+        "-y".to_string(),
+        "-nostats".to_string(),
+        "-progress".to_string(),
+        "pipe:1".to_string(),
+    ]
+}
+
+/// Where a first pass writes its output, only the stats file is kept
+pub fn null_output() -> &'static str {
+    if env::consts::OS == "windows" {
+        "nul"
+    } else {
+        "/dev/null"
+    }
+}
 
-    // 6. Filtergraph zinciri - doğru sıralama ÇOK önemli
+/// Filters every encode starts with. Non-square pixels are stretched to `sar_scale`,
+/// the display size, before the crop so the crop percentages line up with the frame
+/// the editor showed.
+pub fn geometry_filters(sar_scale: Option<(u32, u32)>, crop: Option<PixelCrop>) -> Vec<String> {
     let mut filters = Vec::new();
+    if let Some((width, height)) = sar_scale {
+        filters.push(format!("scale={}:{}", width, height));
+        filters.push("setsar=1".to_string());
+    }
+    if let Some(crop) = crop {
+        filters.push(crop.filter());
+    }
+    filters
+}
 
-    // Crop filter MUTLAKA ilk sırada
-    let mut _has_crop = false;
-    if let Some(ref crop_settings) = crop {
-        if let Some(crop_map) = crop_settings.as_object() {
-            if let (Some(x), Some(y), Some(width), Some(height)) = (
-                crop_map.get("x").and_then(|v| v.as_f64()),
-                crop_map.get("y").and_then(|v| v.as_f64()),
-                crop_map.get("width").and_then(|v| v.as_f64()),
-                crop_map.get("height").and_then(|v| v.as_f64())
-            ) {
-                // Crop sadece geçerli değerler için uygula
-                if width > 0.0 && height > 0.0 && width < 100.0 && height < 100.0 {
-                    // Referans boyutu
-                    let video_width = 1280.0;  // Referans genişlik
-                    let video_height = 720.0;  // Referans yükseklik
-
-                    // Piksel değerlerine çevir - video boyutuna göre piksel hesapla
-                    let x_px = (x / 100.0 * video_width).round();
-                    let y_px = (y / 100.0 * video_height).round();
-                    let width_px = (width / 100.0 * video_width).round();
-                    let height_px = (height / 100.0 * video_height).round();
-
-                    // Boyut sınırlarını kontrol et - hata riskini azalt
-                    if width_px >= 16.0 && height_px >= 16.0 {
-                        // FFmpeg crop formatı: crop=width:height:x:y
-                        let crop_filter = format!("crop={}:{}:{}:{}",
-                            width_px, height_px, x_px, y_px);
-                        filters.push(crop_filter);
-                        _has_crop = true;
-
-                        println!("Crop uygulanıyor: {}:{}:{}:{} (x={}, y={}, w={}, h={})",
-                            width_px, height_px, x_px, y_px, x, y, width, height);
-                    } else {
-                        println!("Çok küçük crop boyutları, atlanıyor: {}x{}", width_px, height_px);
-                    }
-                } else {
-                    println!("Geçersiz crop yüzdeleri, atlanıyor: {}x{}", width, height);
-                }
+/// A two-pass encode. Pass 1 runs exactly once and sees the same
+/// input, trim and filter chain as pass 2, otherwise its stats don't match.
+pub struct TwoPassEncode {
+    pub input: String,
+    pub output: String,
+    pub codec: VideoCodec,
+    /// ffmpeg encoder for the codec, see `VideoCodec::find_encoder`
+    pub encoder: String,
+    /// kib/s
+    pub video_bitrate: f32,
+    /// `None` when the source has no audio or it is stripped, the output gets no audio track
+    pub audio: Option<AudioSettings>,
+    pub start_time: Option<f32>,
+    pub end_time: Option<f32>,
+    /// two or more parts joined with `concat`, empty encodes `start_time` to `end_time`
+    pub ranges: Vec<TimeRange>,
+    /// several inputs scaled to `format` and joined, `input` and the trim are not used then
+    pub clips: Vec<MergeClip>,
+    pub crop: Option<PixelCrop>,
+    /// see `geometry_filters`
+    pub sar_scale: Option<(u32, u32)>,
+    /// resolution and frame rate picked by `get_output_format`,
+    /// `None` keeps the source as it is
+    pub format: Option<OutputFormat>,
+    /// see `codec::video_args`
+    pub passlog_prefix: String,
+}
+
+impl TwoPassEncode {
+    /// Runs both passes, returns the size of the output in bytes
+    pub fn run(
+        &self,
+        progress: &mut ProgressReporter,
+        job: &JobHandle,
+    ) -> Result<u64, ConversionError> {
+        // Create a PathBuf from the output string
+        let output_path = PathBuf::from(&self.output);
+
+        // Create the parent directory if it doesn't exist
+        if let Some(parent_dir) = output_path.parent() {
+            create_dir_all(parent_dir).map_err(|e| io_error(parent_dir, e))?;
+        }
+
+        // İlk geçiş - video analizi için, tek geçişli encoder'larda atlanır
+        if self.is_two_pass() {
+            eprintln!("İlk geçiş başlatılıyor...");
+            let args = self.pass_args(1);
+            eprintln!("İlk geçiş FFmpeg komutu: {}", args.join(" "));
+            run_pass(1, args, progress, job)?;
+        }
+
+        eprintln!("İkinci geçiş başlatılıyor...");
+        let args = self.pass_args(2);
+        eprintln!("FFmpeg ikinci geçiş komutu: {}", args.join(" "));
+        run_pass(2, args, progress, job)?;
+
+        // Output dosyayı kontrol et
+        progress.report(ConversionProgress::verifying());
+        check_output(&self.output)
+    }
+
+    /// false for encoders that only run the second pass, see `codec::is_two_pass`
+    pub fn is_two_pass(&self) -> bool {
+        is_two_pass(&self.encoder)
+    }
+
+    /// kib/s, 0 without an audio track
+    pub fn audio_rate(&self) -> f32 {
+        self.audio.map(|audio| audio.bitrate).unwrap_or(0.00)
+    }
+
+    /// Builds the complete ffmpeg command line of a pass
+    fn pass_args(&self, pass: u8) -> Vec<String> {
+        // 1. Global parametreler, ilerleme bilgisi stdout'a yazılır
+        let mut args = progress_args();
+
+        // 2. Input ve kesme parametreleri, iki geçişte de aynı
+        args.extend(self.input_args());
+
+        // 3. Video codec ve iki geçiş parametreleri, her encoder'ın kendi seçenekleri var
+        args.extend(video_args(&self.encoder, pass, &self.passlog_prefix));
+        let container = self.codec.container();
+
+        // 4. Filtre zinciri, ilk geçiş de crop dahil aynı zinciri kullanmalı
+        if !self.clips.is_empty() {
+            // Videolar aynı boyut, fps ve örnekleme hızına getirilip birleştirilir
+            let audio = pass == 2 && self.audio.is_some();
+            let graph = self.merge_graph(audio);
+            eprintln!("FFmpeg birleştirme grafiği: {}", graph);
+            args.extend(vec!["-filter_complex".to_string(), graph]);
+            args.extend(vec!["-map".to_string(), "[v]".to_string()]);
+            if audio {
+                args.extend(vec!["-map".to_string(), "[a]".to_string()]);
+            }
+        } else if self.ranges.is_empty() {
+            let filter_chain = self.filter_chain();
+            if !filter_chain.is_empty() {
+                eprintln!("FFmpeg filtre zinciri: {}", filter_chain);
+                args.push("-filter:v".to_string());
+                args.push(filter_chain);
+            }
+        } else {
+            // Parçalar birleştirilir, ilk geçişte ses olmadığı için sadece video birleştirilir
+            let audio = pass == 2 && self.audio.is_some();
+            let graph = self.segments_graph(audio);
+            eprintln!("FFmpeg filtre grafiği: {}", graph);
+            args.extend(vec!["-filter_complex".to_string(), graph]);
+            args.extend(vec!["-map".to_string(), "[v]".to_string()]);
+            if audio {
+                args.extend(vec!["-map".to_string(), "[a]".to_string()]);
             }
         }
+
+        // 5. Bit hızı
+        args.extend(vec!["-b:v".to_string(), format!("{}k", self.video_bitrate)]);
+
+        if pass == 1 {
+            // İlk geçiş sadece analiz, ses ve çıktı dosyası yok
+            args.extend(vec![
+                "-an".to_string(),
+                "-f".to_string(),
+                container.format().to_string(),
+                "-pix_fmt".to_string(),
+                "yuv420p".to_string(),
+                null_output().to_string(),
+            ]);
+            return args;
+        }
+
+        // 6. Ses parametreleri, bütçeye göre get_audio_settings seçer.
+        // Ses yoksa hiçbir ses seçeneği verilmez, "-c:a aac -b:a copy" gibi geçersiz kombinasyonlar oluşmaz
+        match &self.audio {
+            Some(audio) => args.extend(audio.args()),
+            None => args.push("-an".to_string()),
+        }
+
+        // 7. Video format parametreleri - video player'larda daha iyi oynatılması için
+        if container == Container::Mp4 {
+            args.extend(vec!["-movflags".to_string(), "+faststart".to_string()]);
+        }
+        args.extend(vec![
+            "-f".to_string(),
+            container.format().to_string(),
+            "-pix_fmt".to_string(),
+            "yuv420p".to_string(),
+        ]);
+
+        // 8. Output dosyası
+        args.push(self.output.clone());
+
+        args
     }
 
-    // Scale filter her zaman croptan sonra
-    // Crop yoksa -2 ile otomatik yükseklik hesapla
-    // Crop varsa, seçilen bölge doğru boyutta gösterilsin
-    if _has_crop {
-        filters.push("scale=1280:-2".to_string());
-    } else {
-        filters.push("scale=1280:-2".to_string());
-    }
-
-    // 7. Filtre zincirini ekle
-    let filter_chain = filters.join(",");
-
-    // Filtreleri yalnızca geçerli olduğunda ekle
-    if !filter_chain.is_empty() {
-        args.push("-filter:v".to_string());
-        args.push(filter_chain.clone());
-        println!("FFmpeg filtre zinciri: {}", filter_chain);
-    }
-
-    // 8. İkinci geçiş parametreleri
-    args.extend(vec![
-        "-b:v".to_string(),
-        bitrate_str,
-        "-pass".to_string(),
-        "2".to_string(),
-    ]);
-
-    // 9. Ses parametreleri
-    args.extend(vec![
-        "-c:a".to_string(),
-        "aac".to_string(),
-        "-b:a".to_string(),
-        abi,
-    ]);
-
-    // 10. Video format parametreleri - video player'larda daha iyi oynatılması için
-    args.extend(vec![
-        "-movflags".to_string(),
-        "+faststart".to_string(),
-        "-f".to_string(),
-        "mp4".to_string(),
-        "-pix_fmt".to_string(),
-        "yuv420p".to_string(),
-    ]);
-
-    // 11. Output dosyası
-    args.push(output.to_string());
-
-    // FFmpeg komutunu yazdır
-    let cmd_str = args.join(" ");
-    println!("FFmpeg ikinci geçiş komutu: {}", cmd_str);
-
-    // Komutu çalıştır ve hataları yakala
-    match Command::new_sidecar("ffmpeg")
-        .expect("failed to get ffmpeg sidecar")
-        .args(args)
-        .output() {
-            Ok(cmd_output) => {
-                println!("İkinci geçiş tamamlandı - çıktı kontrol ediliyor");
-
-                // Hata çıktısını göster (önemli)
-                let stderr_str = String::from_utf8_lossy(cmd_output.stderr.as_bytes());
-                if !stderr_str.is_empty() {
-                    println!("FFmpeg stderr tam çıktı:");
-                    println!("------- STDERR BAŞLANGICI -------");
-                    println!("{}", stderr_str);
-                    println!("------- STDERR SONU -------");
+    fn input_args(&self) -> Vec<String> {
+        // Birleştirmede her girdi kendi kesmesiyle okunur, -t burada girdi seçeneği
+        if !self.clips.is_empty() {
+            let mut args = Vec::new();
+            for clip in self.clips.iter() {
+                if clip.start > 0.0 {
+                    args.extend(vec!["-ss".to_string(), clip.start.to_string()]);
                 }
+                args.extend(vec![
+                    "-t".to_string(),
+                    clip.duration.to_string(),
+                    "-i".to_string(),
+                    clip.input.clone(),
+                ]);
+            }
+            return args;
+        }
+
+        match (self.ranges.first(), self.ranges.last()) {
+            // İlk parçanın başına atlanır, son parçanın sonundan sonrası okunmaz
+            (Some(first), Some(last)) => input_args(&self.input, Some(first.start), Some(last.end)),
+            _ => input_args(&self.input, self.start_time, self.end_time),
+        }
+    }
+
+    /// `trim`/`atrim` for every kept range joined with `concat`, followed by the video
+    /// filters. The outputs are `[v]` and, with `audio`, `[a]`.
+    fn segments_graph(&self, audio: bool) -> String {
+        // -ss ile ilk parçanın başına atlandığı için zamanlar ona göre kaydırılır
+        let offset = self.ranges.first().map(|range| range.start).unwrap_or(0.0);
+        let mut graph = Vec::new();
+        let mut inputs = String::new();
+
+        for (i, range) in self.ranges.iter().enumerate() {
+            let (start, end) = (range.start - offset, range.end - offset);
+            graph.push(format!(
+                "[0:v:0]trim=start={}:end={},setpts=PTS-STARTPTS[v{}]",
+                start, end, i
+            ));
+            inputs.push_str(&format!("[v{}]", i));
+
+            if audio {
+                graph.push(format!(
+                    "[0:a:0]atrim=start={}:end={},asetpts=PTS-STARTPTS[a{}]",
+                    start, end, i
+                ));
+                inputs.push_str(&format!("[a{}]", i));
+            }
+        }
 
-                // Output dosyayı kontrol et
-                let output_path = Path::new(&output);
-                if output_path.exists() {
-                    if let Ok(metadata) = std::fs::metadata(&output) {
-                        println!("Output dosya boyutu: {} bytes", metadata.len());
-                        if metadata.len() == 0 {
-                            println!("HATA: Output dosya 0 byte! FFmpeg bir hata oluşturmuş olabilir.");
-                        } else {
-                            println!("Başarılı: Dosya boyutu > 0");
-                        }
-                    }
+        let chain = self.filter_chain();
+        let video_out = if chain.is_empty() { "[v]" } else { "[joined]" };
+        graph.push(format!(
+            "{}concat=n={}:v=1:a={}{}{}",
+            inputs,
+            self.ranges.len(),
+            audio as u8,
+            video_out,
+            if audio { "[a]" } else { "" }
+        ));
+        if !chain.is_empty() {
+            graph.push(format!("[joined]{}[v]", chain));
+        }
+
+        graph.join(";")
+    }
+
+    /// Every clip letterboxed into the output size at the output frame rate, audio
+    /// resampled to the output rate and layout, then joined with `concat`.
+    /// The outputs are `[v]` and, with `audio`, `[a]`.
+    fn merge_graph(&self, audio: bool) -> String {
+        let format = self.format.unwrap_or(OutputFormat {
+            width: 1280,
+            height: 720,
+            fps: None,
+            bits_per_pixel: 0.0,
+        });
+        let (width, height) = (format.width, format.height);
+        let fps = format.fps.unwrap_or(30.0);
+        let (sample_rate, layout) = match self.audio {
+            Some(settings) if settings.channels == 1 => (settings.sample_rate, "mono"),
+            Some(settings) => (settings.sample_rate, "stereo"),
+            None => (48000, "stereo"),
+        };
+
+        let mut graph = Vec::new();
+        let mut inputs = String::new();
+        for (i, clip) in self.clips.iter().enumerate() {
+            // Kare olmayan pikseller önce düzeltilir, sonra en-boy oranı korunup siyah bantla doldurulur
+            graph.push(format!(
+                "[{i}:v:0]scale=iw*sar:ih,setsar=1,\
+                 scale={w}:{h}:force_original_aspect_ratio=decrease,\
+                 pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps},format=yuv420p[v{i}]",
+                i = i,
+                w = width,
+                h = height,
+                fps = fps
+            ));
+            inputs.push_str(&format!("[v{}]", i));
+
+            if audio {
+                if clip.audio {
+                    graph.push(format!(
+                        "[{i}:a:0]aformat=sample_rates={rate}:channel_layouts={layout},\
+                         asetpts=PTS-STARTPTS[a{i}]",
+                        i = i,
+                        rate = sample_rate,
+                        layout = layout
+                    ));
                 } else {
-                    println!("HATA: Output dosya oluşturulamadı!");
+                    // Sesi olmayan girdi için süresi kadar sessizlik
+                    graph.push(format!(
+                        "anullsrc=r={}:cl={},atrim=duration={}[a{}]",
+                        sample_rate, layout, clip.duration, i
+                    ));
                 }
-            },
-            Err(e) => {
-                println!("FFmpeg ikinci geçiş hatası: {}", e);
-                panic!("FFmpeg ikinci geçiş hatası: {}", e);
+                inputs.push_str(&format!("[a{}]", i));
             }
-        };
+        }
+
+        graph.push(format!(
+            "{}concat=n={}:v=1:a={}[v]{}",
+            inputs,
+            self.clips.len(),
+            audio as u8,
+            if audio { "[a]" } else { "" }
+        ));
+        graph.join(";")
+    }
+
+    /// Crop (if any) followed by the scale filter, the order matters
+    fn filter_chain(&self) -> String {
+        // Kare olmayan pikseller önce düzeltilir, crop yüzdeleri ekranda görünen kareye göre
+        let mut filters = geometry_filters(self.sar_scale, self.crop);
+
+        // Scale ve fps filtreleri her zaman croptan sonra
+        if let Some(format) = self.format {
+            filters.push(format!("scale={}:{}", format.width, format.height));
+            if let Some(fps) = format.fps {
+                filters.push(format!("fps={}", fps));
+            }
+        }
+
+        filters.join(",")
+    }
+}
+
+/// Remuxes the trimmed part of a source that already fits the target, nothing is
+/// re-encoded. With `-c copy` the cut snaps to the keyframe before `start_time`.
+pub struct StreamCopy {
+    pub input: String,
+    pub output: String,
+    pub codec: VideoCodec,
+    pub start_time: Option<f32>,
+    pub end_time: Option<f32>,
+    /// `false` drops the audio track
+    pub audio: bool,
 }
 
-pub fn get_output(input: &str) -> String {
-    let file_path = Path::new(input);
-    let user_dirs = UserDirs::new().expect("Failed to find user dirs");
+impl StreamCopy {
+    /// Runs the copy, returns the size of the output in bytes
+    pub fn run(
+        &self,
+        progress: &mut ProgressReporter,
+        job: &JobHandle,
+    ) -> Result<u64, ConversionError> {
+        progress.start_single_pass(ConversionStage::Copying, 1);
+
+        let args = self.args();
+        eprintln!("Kopyalama FFmpeg komutu: {}", args.join(" "));
+        run_pass(1, args, progress, job)?;
+
+        progress.report(ConversionProgress::verifying());
+        check_output(&self.output)
+    }
 
-    let vid_dir = match user_dirs.video_dir() {
-        Some(vid_dir) => vid_dir.as_os_str().to_str().unwrap(),
-        _ => {
-            // if video dir fails, use the parent dir of the clip
-            match file_path.parent() {
-                Some(dir) => dir.as_os_str().to_str().unwrap(),
-                // use current dir
-                _ => ".",
+    fn args(&self) -> Vec<String> {
+        let mut args = progress_args();
+        args.extend(input_args(&self.input, self.start_time, self.end_time));
+
+        args.extend(vec!["-c".to_string(), "copy".to_string()]);
+        // Altyazı ve veri akışları kapsayıcıya uymayabilir, kopyalanmaz
+        args.extend(vec!["-sn".to_string(), "-dn".to_string()]);
+        if !self.audio {
+            args.push("-an".to_string());
+        }
+        // Anahtar kareden başlayan kesimde zaman damgaları sıfırdan başlatılır
+        args.extend(vec!["-avoid_negative_ts".to_string(), "make_zero".to_string()]);
+
+        let container = self.codec.container();
+        if self.codec == VideoCodec::H265 {
+            // Apple oynatıcıları H.265'i sadece hvc1 etiketiyle açar
+            args.extend(vec!["-tag:v".to_string(), "hvc1".to_string()]);
+        }
+        if container == Container::Mp4 {
+            args.extend(vec!["-movflags".to_string(), "+faststart".to_string()]);
+        }
+        args.extend(vec![
+            "-f".to_string(),
+            container.format().to_string(),
+            self.output.clone(),
+        ]);
+
+        args
+    }
+}
+
+/// Runs `run` until the output fits `limit` bytes. After every attempt that came out too
+/// big `shrink` lowers the settings for the size it got, false when nothing can be lowered
+/// anymore. `single_pass` is the progress stage of a single pass encode, `None` for two
+/// passes. Gives up after `max_attempts`, returns the final size in bytes.
+pub fn shrink_until_fits<T>(
+    encode: &mut T,
+    limit: u64,
+    max_attempts: u32,
+    single_pass: Option<ConversionStage>,
+    progress: &mut ProgressReporter,
+    mut run: impl FnMut(&T, &mut ProgressReporter) -> Result<u64, ConversionError>,
+    mut shrink: impl FnMut(&mut T, u64) -> bool,
+) -> Result<u64, ConversionError> {
+    let mut attempt = 1;
+
+    loop {
+        match single_pass {
+            Some(stage) => progress.start_single_pass(stage, attempt),
+            None => progress.start_attempt(attempt),
+        }
+        eprintln!("{}. deneme, sınır: {} bytes", attempt, limit);
+
+        let size = run(encode, progress)?;
+        if size <= limit {
+            return Ok(size);
+        }
+        eprintln!("Çıktı {} bytes, sınır {} bytes", size, limit);
+
+        // Son denemeden sonra ya da düşürülecek ayar kalmadıysa vazgeçilir
+        if attempt >= max_attempts || !shrink(encode, size) {
+            return Err(ConversionError::OutputTooLarge {
+                size,
+                target: limit,
+                attempts: attempt,
+            });
+        }
+        attempt += 1;
+    }
+}
+
+/// Runs the encode and checks the output against the target size. When it came out too
+/// big (or a lot smaller than needed) the bit rate is corrected and the encode repeated,
+/// up to `MAX_SIZE_ATTEMPTS` times. An undershoot is kept next to the output and
+/// restored when the bigger retry overshoots. Returns the final size in bytes.
+pub fn encode_to_size(
+    encode: &mut TwoPassEncode,
+    target_size: f32,
+    duration: f32,
+    progress: &mut ProgressReporter,
+    job: &JobHandle,
+) -> Result<u64, ConversionError> {
+    let target_bytes = get_target_bytes(target_size);
+    let mut attempt = 1;
+    // Hedefin altında kalan en iyi sonuç, daha yüksek bit hızıyla tekrar denenirken saklanır
+    let mut best: Option<(String, u64)> = None;
+
+    loop {
+        if encode.is_two_pass() {
+            progress.start_attempt(attempt);
+        } else {
+            progress.start_single_pass(ConversionStage::Encoding, attempt);
+        }
+        eprintln!(
+            "{}. deneme, video bit hızı: {}k, hedef: {} bytes",
+            attempt, encode.video_bitrate, target_bytes
+        );
+
+        let size = encode.run(progress, job)?;
+        let too_large = size > target_bytes;
+        // Küçük kalma durumunda sadece bir kez yeniden denenir, bazı videolar bit hızını dolduramaz
+        let too_small = attempt == 1 && (size as f64) < target_bytes as f64 * UNDERSHOOT_RATIO;
+
+        if !too_large && !too_small {
+            return Ok(size);
+        }
+
+        // Geçerli bir sonuç varken hedef aşıldıysa bit hızı artık yükseltilmez, saklanan sonuç kullanılır
+        if too_large {
+            if let Some((path, best_size)) = best.take() {
+                eprintln!(
+                    "Çıktı {} bytes, hedefi aşıyor. Önceki {} bytes sonuç kullanılıyor",
+                    size, best_size
+                );
+                rename(&path, &encode.output)?;
+                return Ok(best_size);
             }
         }
-    };
 
-    let file_name = match file_path.file_stem() {
-        Some(name) => name.to_str().unwrap(),
-        _ => {
-            panic!("No file name")
+        if attempt >= MAX_SIZE_ATTEMPTS {
+            if too_large {
+                return Err(ConversionError::OutputTooLarge {
+                    size,
+                    target: target_bytes,
+                    attempts: attempt,
+                });
+            }
+            return Ok(size);
+        }
+
+        let corrected = get_corrected_video_rate(
+            encode.video_bitrate,
+            encode.audio_rate(),
+            duration,
+            size,
+            target_bytes,
+        );
+        eprintln!(
+            "Çıktı {} bytes, hedef {} bytes. Yeni video bit hızı: {}k",
+            size, target_bytes, corrected
+        );
+
+        if corrected <= 0.0 {
+            return Err(ConversionError::OutputTooLarge {
+                size,
+                target: target_bytes,
+                attempts: attempt,
+            });
+        }
+
+        if too_small {
+            let path = format!("{}.best", encode.output);
+            rename(&encode.output, &path)?;
+            best = Some((path, size));
         }
+
+        encode.video_bitrate = corrected;
+        attempt += 1;
+    }
+}
+
+fn rename(from: &str, to: &str) -> Result<(), ConversionError> {
+    std::fs::rename(from, to).map_err(|e| io_error(Path::new(to), e))
+}
+
+/// Formats of an audio-only export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    Mp3,
+    Opus,
+    Aac,
+    Flac,
+}
+
+impl AudioFormat {
+    /// Lossy codec of the format, `None` for FLAC which has no bit rate to aim for
+    pub fn codec(&self) -> Option<AudioCodec> {
+        match self {
+            AudioFormat::Mp3 => Some(AudioCodec::Mp3),
+            AudioFormat::Opus => Some(AudioCodec::Opus),
+            AudioFormat::Aac => Some(AudioCodec::Aac),
+            AudioFormat::Flac => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Aac => "m4a",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    /// Name for ffmpeg's `-f`
+    fn format(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Opus => "ogg",
+            // M4A, AAC'nin ham ADTS halinden daha çok yerde oynatılır
+            AudioFormat::Aac => "ipod",
+            AudioFormat::Flac => "flac",
+        }
+    }
+}
+
+/// Bit rate, channels and sample rate of an audio-only export. The whole budget goes
+/// to the audio, kept under the codec's ceiling and the source. `None` for FLAC.
+pub fn get_audio_only_settings(
+    format: AudioFormat,
+    target_size: f32,
+    duration: f32,
+    source: &AudioStream,
+) -> Result<Option<AudioSettings>, ConversionError> {
+    let codec = match format.codec() {
+        Some(codec) => codec,
+        None => return Ok(None),
     };
 
-    let file_out = format!("{}-8m.mp4", file_name);
-    let output_path = Path::new(vid_dir)
-        .join(file_out)
-        .as_os_str()
-        .to_str()
-        .unwrap()
-        .to_string();
+    // Videodaki gibi: en düşük ses bit hızının kapladığı MB hedefle karşılaştırılır
+    let min_size = get_target_size(codec.min_bitrate(), duration);
+    if !is_minsize(min_size, target_size) {
+        return Err(ConversionError::TargetTooSmall {
+            min_size,
+            target_size,
+        });
+    }
+
+    // Çıktının tamamı ses, toplam bit hızı sese kalır
+    let rate = get_total_rate(target_size, duration) * RETRY_SAFETY as f32;
+
+    let mut bitrate = rate.clamp(codec.min_bitrate(), codec.max_bitrate());
+    if let Some(source_rate) = source.bit_rate {
+        bitrate = bitrate.min((source_rate as f32 / 1024.0).max(codec.min_bitrate()));
+    }
+
+    // Yuvarlama hedefi aşmasın diye aşağı yuvarlanır
+    Ok(Some(get_audio_format(codec, bitrate.floor(), Some(source))))
+}
+
+/// Single pass export of one audio stream, the video and everything else is dropped
+pub struct AudioExtract {
+    pub input: String,
+    pub output: String,
+    pub format: AudioFormat,
+    /// 0 is the first audio stream
+    pub stream: u32,
+    pub start_time: Option<f32>,
+    pub end_time: Option<f32>,
+    /// `None` for FLAC, it is lossless and keeps the source channels and sample rate
+    pub settings: Option<AudioSettings>,
+}
+
+impl AudioExtract {
+    /// Runs the encode, returns the size of the output in bytes
+    pub fn run(
+        &self,
+        progress: &mut ProgressReporter,
+        job: &JobHandle,
+    ) -> Result<u64, ConversionError> {
+        let args = self.args();
+        eprintln!("Ses FFmpeg komutu: {}", args.join(" "));
+        run_pass(1, args, progress, job)?;
+
+        progress.report(ConversionProgress::verifying());
+        check_output(&self.output)
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = progress_args();
+        args.extend(input_args(&self.input, self.start_time, self.end_time));
+
+        // Sadece seçilen ses akışı, kapak resmi dahil görüntü ve altyazılar alınmaz
+        args.extend(vec![
+            "-map".to_string(),
+            format!("0:a:{}", self.stream),
+            "-vn".to_string(),
+            "-sn".to_string(),
+            "-dn".to_string(),
+            "-map_metadata".to_string(),
+            "0".to_string(),
+        ]);
+
+        match &self.settings {
+            Some(settings) => args.extend(settings.args()),
+            None => args.extend(vec![
+                "-c:a".to_string(),
+                "flac".to_string(),
+                "-compression_level".to_string(),
+                "8".to_string(),
+            ]),
+        }
+
+        if self.format == AudioFormat::Aac {
+            args.extend(vec!["-movflags".to_string(), "+faststart".to_string()]);
+        }
+        args.extend(vec![
+            "-f".to_string(),
+            self.format.format().to_string(),
+            self.output.clone(),
+        ]);
+        args
+    }
+}
+
+/// Encodes the audio and lowers the bit rate after every attempt that came out too big,
+/// up to `MAX_SIZE_ATTEMPTS` times. FLAC has no bit rate and is encoded once.
+/// Returns the final size in bytes.
+pub fn encode_audio_to_size(
+    extract: &mut AudioExtract,
+    target_size: f32,
+    progress: &mut ProgressReporter,
+    job: &JobHandle,
+) -> Result<u64, ConversionError> {
+    let target_bytes = get_target_bytes(target_size);
+
+    shrink_until_fits(
+        extract,
+        target_bytes,
+        MAX_SIZE_ATTEMPTS,
+        Some(ConversionStage::Encoding),
+        progress,
+        |extract, progress| {
+            eprintln!("Ses ayarları: {:?}", extract.settings);
+            extract.run(progress, job)
+        },
+        |extract, size| {
+            // FLAC'ın bit hızı yok, küçültülemez
+            let settings = match extract.settings.as_mut() {
+                Some(settings) => settings,
+                None => return false,
+            };
+
+            // Ses neredeyse sabit bit hızında, boyut bit hızıyla orantılı
+            let corrected = (settings.bitrate as f64 * target_bytes as f64 * RETRY_SAFETY
+                / size as f64)
+                .floor() as f32;
+            settings.bitrate = corrected;
+            corrected >= settings.codec.min_bitrate()
+        },
+    )
+}
+
+/// Makes sure ffmpeg actually produced a non-empty file, returns its size in bytes
+pub fn check_output(output: &str) -> Result<u64, ConversionError> {
+    let output_path = Path::new(output);
+    if !output_path.exists() {
+        eprintln!("HATA: Output dosya oluşturulamadı!");
+        return Err(ConversionError::OutputMissing {
+            path: output.to_string(),
+        });
+    }
+
+    let metadata = std::fs::metadata(output_path).map_err(|e| io_error(output_path, e))?;
+    eprintln!("Output dosya boyutu: {} bytes", metadata.len());
+
+    if metadata.len() == 0 {
+        eprintln!("HATA: Output dosya 0 byte! FFmpeg bir hata oluşturmuş olabilir.");
+        return Err(ConversionError::OutputEmpty {
+            path: output.to_string(),
+        });
+    }
+
+    Ok(metadata.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(width: u32, height: u32) -> VideoStream {
+        VideoStream {
+            index: 0,
+            codec: "h264".to_string(),
+            profile: None,
+            width,
+            height,
+            rotation: 0,
+            sample_aspect_ratio: None,
+            fps: Some(30.0),
+            pix_fmt: Some("yuv420p".to_string()),
+            color_space: None,
+            color_transfer: None,
+            color_primaries: None,
+            color_range: None,
+            bit_rate: None,
+            language: None,
+        }
+    }
+
+    fn crop(x: f64, y: f64, width: f64, height: f64) -> Crop {
+        Crop {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn output_format_never_upscales() {
+        let format = get_output_format(100_000.0, (640, 360), Some(30.0), VideoCodec::H264, None);
+        assert_eq!((format.width, format.height, format.fps), (640, 360, None));
+    }
+
+    #[test]
+    fn output_format_rounds_odd_sizes_down_to_even() {
+        let format = get_output_format(100_000.0, (1281, 721), Some(30.0), VideoCodec::H264, None);
+        assert_eq!((format.width, format.height), (1280, 720));
+    }
+
+    #[test]
+    fn output_format_lowers_fps_before_resolution() {
+        // 1080p 30 fps için yetmez, 24 fps için yeter
+        let format = get_output_format(2700.0, (1920, 1080), Some(30.0), VideoCodec::H264, None);
+        assert_eq!(
+            (format.width, format.height, format.fps),
+            (1920, 1080, Some(24.0))
+        );
+
+        // 1080p 24 fps için de yetmez, 720p 30 fps için yeter
+        let format = get_output_format(2000.0, (1920, 1080), Some(30.0), VideoCodec::H264, None);
+        assert_eq!((format.width, format.height, format.fps), (1280, 720, None));
+        assert!(format.bits_per_pixel >= MIN_BITS_PER_PIXEL);
+    }
+
+    #[test]
+    fn output_format_keeps_the_preset_limit() {
+        let format = get_output_format(
+            100_000.0,
+            (1920, 1080),
+            Some(30.0),
+            VideoCodec::H264,
+            Some(480),
+        );
+        assert_eq!((format.width, format.height), (852, 480));
+    }
+
+    #[test]
+    fn output_format_falls_back_to_the_smallest_step() {
+        let format = get_output_format(1.0, (1920, 1080), Some(30.0), VideoCodec::H264, None);
+        assert_eq!(
+            (format.width, format.height, format.fps),
+            (640, 360, Some(24.0))
+        );
+    }
+
+    #[test]
+    fn pixel_crop_uses_the_source_size() {
+        let pixel_crop = get_pixel_crop(&crop(10.0, 10.0, 50.0, 50.0), &video(1920, 1080));
+        assert_eq!(
+            pixel_crop,
+            Some(PixelCrop {
+                x: 192,
+                y: 108,
+                width: 960,
+                height: 540,
+            })
+        );
+    }
+
+    #[test]
+    fn pixel_crop_rounds_down_to_even() {
+        let pixel_crop = get_pixel_crop(&crop(12.5, 12.5, 33.3, 33.3), &video(1001, 601)).unwrap();
+        assert_eq!(
+            pixel_crop,
+            PixelCrop {
+                x: 124,
+                y: 74,
+                width: 332,
+                height: 200,
+            }
+        );
+    }
+
+    #[test]
+    fn pixel_crop_is_clamped_to_the_frame() {
+        let pixel_crop = get_pixel_crop(&crop(80.0, 90.0, 50.0, 50.0), &video(1920, 1080));
+        assert_eq!(
+            pixel_crop,
+            Some(PixelCrop {
+                x: 1536,
+                y: 972,
+                width: 384,
+                height: 108,
+            })
+        );
+
+        // Tamamen karenin dışında kalan crop atlanır
+        assert_eq!(
+            get_pixel_crop(&crop(150.0, 0.0, 50.0, 50.0), &video(1920, 1080)),
+            None
+        );
+        assert_eq!(
+            get_pixel_crop(&crop(99.5, 0.0, 50.0, 50.0), &video(1920, 1080)),
+            None
+        );
+    }
+
+    #[test]
+    fn pixel_crop_skips_invalid_percentages() {
+        assert_eq!(
+            get_pixel_crop(&crop(0.0, 0.0, 0.0, 50.0), &video(1920, 1080)),
+            None
+        );
+        assert_eq!(
+            get_pixel_crop(&crop(0.0, 0.0, 100.0, 50.0), &video(1920, 1080)),
+            None
+        );
+        assert_eq!(
+            get_pixel_crop(&crop(10.0, 10.0, 50.0, 50.0), &video(0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn pixel_crop_applies_to_the_rotated_frame() {
+        let mut rotated = video(1920, 1080);
+        rotated.rotation = 90;
+        let pixel_crop = get_pixel_crop(&crop(0.0, 0.0, 50.0, 50.0), &rotated).unwrap();
+        assert_eq!((pixel_crop.width, pixel_crop.height), (540, 960));
+    }
+
+    #[test]
+    fn sar_scale_stretches_non_square_pixels() {
+        assert_eq!(get_sar_scale(&video(1920, 1080)), None);
+
+        let mut anamorphic = video(1440, 1080);
+        anamorphic.sample_aspect_ratio = Some("4:3".to_string());
+        assert_eq!(get_sar_scale(&anamorphic), Some((1920, 1080)));
 
-    output_path
+        let mut pal = video(720, 576);
+        pal.sample_aspect_ratio = Some("10:11".to_string());
+        assert_eq!(get_sar_scale(&pal), Some((654, 576)));
+    }
+
+    #[test]
+    fn small_audio_budget_keeps_aac_in_mp4() {
+        let audio = get_audio_settings(100.0, Container::Mp4, None, None);
+        assert_eq!(audio.codec, AudioCodec::Aac);
+        assert_eq!(
+            (audio.bitrate, audio.channels),
+            (AudioCodec::Aac.min_bitrate(), 1)
+        );
+
+        let audio = get_audio_settings(100.0, Container::Webm, None, None);
+        assert_eq!(audio.codec, AudioCodec::Opus);
+        assert_eq!(
+            (audio.bitrate, audio.channels),
+            (AudioCodec::Opus.min_bitrate(), 1)
+        );
+    }
+
+    #[test]
+    fn unsupported_audio_codec_falls_back_to_the_default() {
+        let audio = get_audio_settings(1000.0, Container::Webm, Some(AudioCodec::Aac), None);
+        assert_eq!((audio.codec, audio.bitrate), (AudioCodec::Opus, 100.0));
+    }
+
+    #[test]
+    fn geometry_filters_stretch_before_the_crop() {
+        assert!(geometry_filters(None, None).is_empty());
+
+        let crop = PixelCrop {
+            x: 0,
+            y: 0,
+            width: 960,
+            height: 540,
+        };
+        assert_eq!(
+            geometry_filters(Some((1920, 1080)), Some(crop)),
+            vec!["scale=1920:1080", "setsar=1", "crop=960:540:0:0"]
+        );
+    }
+
+    /// Fake encode that outputs `sizes` one after the other, returns the result and the attempts
+    fn shrink(sizes: &[u64], limit: u64, max_attempts: u32) -> (Result<u64, ConversionError>, u32) {
+        let mut progress = ProgressReporter::new(1.0, |_| {});
+        let mut attempts = 0;
+        let result = shrink_until_fits(
+            &mut attempts,
+            limit,
+            max_attempts,
+            Some(ConversionStage::Encoding),
+            &mut progress,
+            |attempts, _| Ok(sizes[*attempts as usize]),
+            |attempts, _| {
+                *attempts += 1;
+                (*attempts as usize) < sizes.len()
+            },
+        );
+        (result, attempts + 1)
+    }
+
+    #[test]
+    fn shrink_until_fits_stops_at_the_first_fit() {
+        let (result, attempts) = shrink(&[300, 120, 90, 80], 100, 5);
+        assert_eq!((result.unwrap(), attempts), (90, 3));
+    }
+
+    #[test]
+    fn shrink_until_fits_gives_up() {
+        // Deneme sınırı
+        let (result, attempts) = shrink(&[300, 200, 150, 90], 100, 3);
+        assert!(matches!(
+            result,
+            Err(ConversionError::OutputTooLarge {
+                size: 150,
+                target: 100,
+                attempts: 3,
+            })
+        ));
+        assert_eq!(attempts, 3);
+
+        // Düşürülecek ayar kalmadı
+        let (result, _) = shrink(&[300, 200], 100, 5);
+        assert!(matches!(
+            result,
+            Err(ConversionError::OutputTooLarge {
+                size: 200,
+                attempts: 2,
+                ..
+            })
+        ));
+    }
+
+    fn range(start: f32, end: f32) -> TimeRange {
+        TimeRange { start, end }
+    }
+
+    #[test]
+    fn kept_ranges_are_sorted_and_merged() {
+        let ranges = [
+            range(5.0, 8.0),
+            range(0.0, 3.0),
+            range(2.0, 4.0),
+            range(8.0, 9.0),
+        ];
+        assert_eq!(
+            get_kept_ranges(10.0, &ranges),
+            vec![range(0.0, 4.0), range(5.0, 9.0)]
+        );
+    }
+
+    #[test]
+    fn kept_ranges_drop_empty_and_outside_ones() {
+        let ranges = [
+            range(-2.0, 1.0),
+            range(4.0, 4.0),
+            range(6.0, 3.0),
+            range(12.0, 15.0),
+            range(9.0, 20.0),
+        ];
+        assert_eq!(
+            get_kept_ranges(10.0, &ranges),
+            vec![range(0.0, 1.0), range(9.0, 10.0)]
+        );
+        assert_eq!(get_kept_ranges(10.0, &[]), vec![]);
+    }
+
+    #[test]
+    fn limit_ranges_cuts_from_the_end() {
+        let ranges = [range(0.0, 4.0), range(5.0, 8.0)];
+        assert_eq!(limit_ranges(&ranges, 10.0), ranges.to_vec());
+        assert_eq!(
+            limit_ranges(&ranges, 5.0),
+            vec![range(0.0, 4.0), range(5.0, 6.0)]
+        );
+        assert_eq!(limit_ranges(&ranges, 4.0), vec![range(0.0, 4.0)]);
+        assert_eq!(limit_ranges(&ranges, 0.0), vec![]);
+        assert_eq!(limit_ranges(&[], 5.0), vec![]);
+    }
 }
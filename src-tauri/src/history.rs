@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local};
+
+use crate::codec::VideoCodec;
+use crate::ffmpeg::{AudioSettings, ConversionError, ConversionRequest};
+use crate::queue::JobState;
+use crate::store::{read_json, write_json};
+
+/// Older entries are dropped, the history is for finding recent outputs again
+const MAX_HISTORY: usize = 200;
+
+/// What a conversion was encoded with, filled in by the conversion once it is planned.
+/// Fields stay `None` when it failed before getting that far.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionDetails {
+    /// seconds of the input that were encoded
+    pub clip_duration: Option<f32>,
+    /// output frame size, `None` for audio or when the source size is kept
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// `None` keeps the source frame rate
+    pub fps: Option<f32>,
+    /// `None` without an audio track
+    pub audio: Option<AudioSettings>,
+}
+
+/// A finished conversion, from the queue or the editor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: String,
+    pub input: String,
+    pub output: Option<String>,
+    /// MB
+    pub target_size: f32,
+    pub codec: VideoCodec,
+    /// trim of the request, seconds
+    #[serde(default)]
+    pub start_time: Option<f32>,
+    #[serde(default)]
+    pub end_time: Option<f32>,
+    #[serde(default)]
+    pub details: ConversionDetails,
+    /// done, failed or cancelled
+    pub state: JobState,
+    /// size of the output in bytes
+    pub size: Option<u64>,
+    pub error: Option<String>,
+    /// RFC 3339, local time
+    #[serde(default)]
+    pub started_at: String,
+    /// RFC 3339, local time
+    pub finished_at: String,
+    /// seconds the conversion took
+    #[serde(default)]
+    pub elapsed: f32,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        id: &str,
+        request: &ConversionRequest,
+        result: &Result<String, ConversionError>,
+        details: &ConversionDetails,
+        started_at: DateTime<Local>,
+    ) -> Self {
+        let (state, output, error) = match result {
+            Ok(output) => (JobState::Done, Some(output.clone()), None),
+            Err(ConversionError::Cancelled) => (JobState::Cancelled, None, None),
+            Err(e) => (JobState::Failed, None, Some(e.to_string())),
+        };
+        let size = output
+            .as_ref()
+            .and_then(|output| fs::metadata(output).ok())
+            .map(|meta| meta.len());
+        let finished_at = Local::now();
+
+        HistoryEntry {
+            id: id.to_string(),
+            input: request.input.clone(),
+            output,
+            target_size: request.target_size,
+            codec: request.codec,
+            start_time: request.start_time,
+            end_time: request.end_time,
+            details: details.clone(),
+            state,
+            size,
+            error,
+            started_at: started_at.to_rfc3339(),
+            finished_at: finished_at.to_rfc3339(),
+            elapsed: (finished_at - started_at).num_milliseconds() as f32 / 1000.0,
+        }
+    }
+}
+
+/// Finished conversions, newest first, kept in `history.json` in the app data dir
+#[derive(Default)]
+pub struct History {
+    entries: Mutex<Vec<HistoryEntry>>,
+    /// `None` keeps the history in memory only
+    path: Option<PathBuf>,
+}
+
+impl History {
+    pub fn load(path: PathBuf) -> Self {
+        let entries = read_json(&path).unwrap_or_default();
+        History {
+            entries: Mutex::new(entries),
+            path: Some(path),
+        }
+    }
+
+    pub fn add(&self, entry: HistoryEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(0, entry);
+        entries.truncate(MAX_HISTORY);
+        self.save(&entries);
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clear();
+        self.save(&entries);
+    }
+
+    fn save(&self, entries: &[HistoryEntry]) {
+        if let Some(path) = &self.path {
+            if let Err(e) = write_json(path, &entries) {
+                eprintln!("Geçmiş kaydedilemedi: {} ({})", path.display(), e);
+            }
+        }
+    }
+}
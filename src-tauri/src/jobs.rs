@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::api::process::CommandChild;
+
+/// Handle of a running conversion, shared between the conversion thread and the commands
+/// the frontend uses to cancel or pause it
+#[derive(Default)]
+pub struct JobHandle {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+    /// the ffmpeg process of the pass that is currently running
+    child: Mutex<Option<CommandChild>>,
+}
+
+impl JobHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Remembers the ffmpeg process of the current pass.
+    /// If the job was cancelled while the process was starting, it is killed right away.
+    pub fn attach(&self, child: CommandChild) {
+        let mut current = self.child.lock().unwrap();
+        if self.is_cancelled() {
+            let _ = child.kill();
+            return;
+        }
+
+        *current = Some(child);
+    }
+
+    /// Forgets the ffmpeg process once its pass has finished
+    pub fn detach(&self) {
+        self.child.lock().unwrap().take();
+    }
+
+    /// Marks the job as cancelled and kills the running ffmpeg process
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        if let Some(child) = self.child.lock().unwrap().take() {
+            let pid = child.pid();
+            // Durdurulmuş bir process de kill sinyali ile sonlanır, yine de devam ettiriyoruz
+            if self.paused.swap(false, Ordering::SeqCst) {
+                signal(pid, "-CONT");
+            }
+            if let Err(e) = child.kill() {
+                eprintln!("FFmpeg process sonlandırılamadı ({}): {}", pid, e);
+            }
+        }
+    }
+
+    /// Suspends the running ffmpeg process, returns false when that's not possible
+    pub fn pause(&self) -> bool {
+        let child = self.child.lock().unwrap();
+        match child.as_ref() {
+            Some(child) if !self.is_paused() => {
+                let paused = signal(child.pid(), "-STOP");
+                self.paused.store(paused, Ordering::SeqCst);
+                paused
+            }
+            _ => false,
+        }
+    }
+
+    /// Continues a paused ffmpeg process
+    pub fn resume(&self) -> bool {
+        let child = self.child.lock().unwrap();
+        match child.as_ref() {
+            Some(child) if self.is_paused() => {
+                let resumed = signal(child.pid(), "-CONT");
+                self.paused.store(!resumed, Ordering::SeqCst);
+                resumed
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Sends a job control signal with `kill`, only supported on unix systems
+fn signal(pid: u32, signal: &str) -> bool {
+    if env::consts::OS == "windows" {
+        eprintln!("Process duraklatma Windows'ta desteklenmiyor");
+        return false;
+    }
+
+    match std::process::Command::new("kill")
+        .args([signal, &pid.to_string()])
+        .status()
+    {
+        Ok(status) => status.success(),
+        Err(e) => {
+            eprintln!("kill {} {} çalıştırılamadı: {}", signal, pid, e);
+            false
+        }
+    }
+}
+
+/// All running conversions by job id, kept in the Tauri state
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, Arc<JobHandle>>>,
+}
+
+impl JobRegistry {
+    pub fn register(&self, job_id: &str) -> Arc<JobHandle> {
+        let job = Arc::new(JobHandle::default());
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job_id.to_string(), job.clone());
+        job
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<Arc<JobHandle>> {
+        self.jobs.lock().unwrap().get(job_id).cloned()
+    }
+
+    pub fn remove(&self, job_id: &str) {
+        self.jobs.lock().unwrap().remove(job_id);
+    }
+}
+
+/// Creates an id for jobs started without one
+pub fn new_job_id() -> String {
+    format!("job-{}", chrono::Local::now().format("%Y%m%d%H%M%S%3f"))
+}
@@ -1 +1,14 @@
+pub mod animation;
+pub mod codec;
 pub mod ffmpeg;
+pub mod history;
+pub mod jobs;
+pub mod output;
+pub mod plan;
+pub mod presets;
+pub mod probe;
+pub mod queue;
+pub mod settings;
+pub mod sticker;
+pub mod store;
+pub mod workspace;
@@ -1,41 +1,50 @@
 #![cfg_attr(all(not(debug_assertions), target_os = "windows"), windows_subsystem = "windows")]
+mod cli;
+
+use atem::animation::{encode_animation_to_size, AnimationEncode, AnimationFormat, AnimationSettings};
+use atem::codec::{self, get_video_encoders, AudioCodec, VideoCodec};
 use atem::ffmpeg::{
-    convert_first, convert_out, get_duration, get_original_audio_rate, get_output, get_target_size,
-    get_target_video_rate, is_minsize,
+    encode_audio_to_size, encode_to_size, get_audio_only_settings, get_clip_duration, get_target_bytes,
+    AudioExtract, AudioFormat, Clip, ConversionError, ConversionProgress, ConversionRequest,
+    Crop, ProgressReporter, StreamCopy, TimeRange, TwoPassEncode,
+};
+use atem::history::{ConversionDetails, History, HistoryEntry};
+use atem::jobs::{new_job_id, JobHandle, JobRegistry};
+use atem::output::{get_output, OutputPolicy};
+use atem::plan::{probe_and_plan, ConversionPlan, Estimate};
+use atem::presets::{Preset, PresetRegistry};
+use atem::probe::{self, MediaInfo};
+use atem::queue::{JobProgress, JobQueue, QueueSnapshot, QueuedJob};
+use atem::settings::{LogLevel, Settings, SettingsStore};
+use atem::sticker::{
+    check_sticker, encode_sticker_to_size, get_sticker_end, get_sticker_fps, get_sticker_limit,
+    get_sticker_rate, StickerEncode, STICKER_SIDE,
 };
+use atem::workspace::{sweep_stale_workspaces, Workspace};
 use std::env;
 use std::fs::{OpenOptions, create_dir_all};
 use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
 use tauri::{
     api::{dialog::message, process::Command},
-    Manager,
+    AppHandle, Manager, State,
 };
 
-pub mod ffmpeg;
-
-// Log seviyeleri
-#[derive(Debug, Clone, Copy)]
-enum LogLevel {
-    Info,
-    Warning,
-    Error,
-    Debug,
-}
+// Ayarlardaki log seviyesi, altındaki mesajlar yazılmaz
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Debug as u8);
 
-impl LogLevel {
-    fn as_str(&self) -> &'static str {
-        match self {
-            LogLevel::Info => "INFO",
-            LogLevel::Warning => "WARN",
-            LogLevel::Error => "ERROR",
-            LogLevel::Debug => "DEBUG",
-        }
-    }
+fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
 }
 
 // Loglama için geliştirilmiş yardımcı fonksiyon
 fn log_to_file(message: &str, level: LogLevel, category: &str) {
+    if (level as u8) < LOG_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+
     // Kullanıcı belgelerine özel log klasörü oluştur
     let mut log_dir = if let Some(user_dirs) = directories::UserDirs::new() {
         // Dökümanlar klasörünü bul, yoksa temp klasörünü kullan
@@ -64,11 +73,11 @@ fn log_to_file(message: &str, level: LogLevel, category: &str) {
     let log_file_name = format!("max8videoeditor_{}.log", today);
     log_dir.push(log_file_name);
 
-    // Konsola da yazdır
+    // Konsola da yazdır, stdout komut satırının ilerleme ve sonuç satırlarına kalır
     let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S.%3f").to_string();
     let log_line = format!("[{}] [{}] [{}] {}\n", timestamp, level.as_str(), category, message);
 
-    println!("{}", log_line.trim());
+    eprintln!("{}", log_line.trim());
 
     // Dosyaya yaz
     match OpenOptions::new().create(true).append(true).open(&log_dir) {
@@ -96,16 +105,10 @@ fn log_warning(message: &str, category: &str) {
     log_to_file(message, LogLevel::Warning, category);
 }
 
-#[cfg(debug_assertions)]
 fn log_debug(message: &str, category: &str) {
     log_to_file(message, LogLevel::Debug, category);
 }
 
-#[cfg(not(debug_assertions))]
-fn log_debug(_message: &str, _category: &str) {
-    // Release modunda log_debug işlemi yapılmayacak
-}
-
 // Log çevresinin durumunu yazdıran fonksiyon
 fn log_environment() {
     let os_name = env::consts::OS;
@@ -198,7 +201,7 @@ fn check_video_url(url: &str) -> bool {
 fn open_file_explorer(path: &str, window: tauri::Window) {
     let label = window.label();
     let parent_window = window.get_window(label).unwrap();
-    println!("{}", path);
+    eprintln!("{}", path);
     log_info(&format!("Açılacak dosya: {}", path), "FileExplorer");
 
     match env::consts::OS {
@@ -226,17 +229,358 @@ fn open_file_explorer(path: &str, window: tauri::Window) {
     }
 }
 
-#[tauri::command(async)]
-fn convert_video(
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn convert_video(
     window: tauri::Window,
-    input: &str,
+    jobs: State<'_, JobRegistry>,
+    history: State<'_, History>,
+    presets: State<'_, PresetRegistry>,
+    input: String,
     target_size: f32,
     start_time: Option<f32>,
     end_time: Option<f32>,
-    crop: Option<serde_json::Value>
-) -> String {
+    ranges: Option<Vec<TimeRange>>,
+    clips: Option<Vec<Clip>>,
+    crop: Option<Crop>,
+    codec: Option<VideoCodec>,
+    audio_codec: Option<AudioCodec>,
+    strip_audio: Option<bool>,
+    output_dir: Option<String>,
+    output: Option<OutputPolicy>,
+    preset: Option<String>,
+    animation: Option<AnimationFormat>,
+    sticker: Option<bool>,
+    audio_only: Option<AudioFormat>,
+    audio_stream: Option<u32>,
+    job_id: Option<String>
+) -> Result<String, ConversionError> {
+    let mut request = ConversionRequest {
+        input,
+        target_size,
+        start_time,
+        end_time,
+        ranges: ranges.unwrap_or_default(),
+        clips: clips.unwrap_or_default(),
+        crop,
+        codec: codec.unwrap_or_default(),
+        audio_codec,
+        strip_audio: strip_audio.unwrap_or(false),
+        output_dir,
+        output: output.unwrap_or_default(),
+        preset: None,
+        animation,
+        sticker: sticker.unwrap_or(false),
+        audio_only,
+        audio_stream: audio_stream.unwrap_or(0),
+    };
+    // Platform ayarı boyutu, codec'i ve ses kurallarını belirler
+    if let Some(id) = preset {
+        presets.get(&id)?.apply(&mut request);
+    }
+    let job_id = job_id.unwrap_or_else(new_job_id);
+    let job = jobs.register(&job_id);
+    log_info(&format!("İş başlatıldı: {}", job_id), "Conversion");
+
+    // FFmpeg çıktısı bloklayarak okunuyor, async runtime'ı meşgul etmemek için ayrı thread'de çalıştır
+    let conversion_window = window.clone();
+    let conversion_job_id = job_id.clone();
+    let conversion_request = request.clone();
+    let started_at = chrono::Local::now();
+    let (result, details) = tauri::async_runtime::spawn_blocking(move || {
+        let mut details = ConversionDetails::default();
+        let result = run_conversion(&job, &conversion_job_id, &conversion_request, &mut details, |p: ConversionProgress| {
+            let _ = conversion_window.emit("conversion_progress", p);
+        });
+        (result, details)
+    })
+    .await
+    .unwrap_or_else(|e| (Err(ConversionError::Internal { message: e.to_string() }), ConversionDetails::default()));
+
+    jobs.remove(&job_id);
+    history.add(HistoryEntry::new(&job_id, &request, &result, &details, started_at));
+
+    match &result {
+        Err(ConversionError::Cancelled) => {
+            log_info(&format!("İş iptal edildi: {}", job_id), "Conversion");
+            let _ = window.emit("conversion_cancelled", &job_id);
+        }
+        Err(e) => log_conversion_error(e),
+        Ok(_) => {}
+    }
+
+    result
+}
+
+// Dönüştürmeden önce bit hızını, çözünürlüğü ve hedefin tutup tutmadığını gösterir
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn estimate_conversion(
+    presets: State<'_, PresetRegistry>,
+    input: String,
+    target_size: f32,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+    ranges: Option<Vec<TimeRange>>,
+    clips: Option<Vec<Clip>>,
+    crop: Option<Crop>,
+    codec: Option<VideoCodec>,
+    audio_codec: Option<AudioCodec>,
+    strip_audio: Option<bool>,
+    preset: Option<String>
+) -> Result<Estimate, ConversionError> {
+    let mut request = ConversionRequest {
+        input,
+        target_size,
+        start_time,
+        end_time,
+        ranges: ranges.unwrap_or_default(),
+        clips: clips.unwrap_or_default(),
+        crop,
+        codec: codec.unwrap_or_default(),
+        audio_codec,
+        strip_audio: strip_audio.unwrap_or(false),
+        output_dir: None,
+        output: OutputPolicy::default(),
+        preset: None,
+        animation: None,
+        sticker: false,
+        audio_only: None,
+        audio_stream: 0,
+    };
+    if let Some(id) = preset {
+        presets.get(&id)?.apply(&mut request);
+    }
+
+    let (_, plan) = probe_and_plan(&request)?;
+    Ok(Estimate::new(&plan, &request))
+}
+
+/// Logs the crop the plan ended up with, `get_pixel_crop` itself stays quiet
+fn log_crop(request: &ConversionRequest, plan: &ConversionPlan) {
+    match (request.crop, plan.crop) {
+        (Some(_), Some(crop)) => log_info(&format!("Crop uygulanıyor: {}", crop.filter()), "Conversion"),
+        (Some(crop), None) => log_warning(&format!("Crop geçersiz ya da çok küçük, atlanıyor: {:?}", crop), "Conversion"),
+        _ => {}
+    }
+}
+
+fn log_conversion_error(e: &ConversionError) {
+    log_error(&format!("Video dönüştürme başarısız: {}", e), "Conversion");
+    if let Some(stderr) = e.stderr() {
+        log_debug(&format!("FFmpeg stderr:\n{}", stderr), "Conversion");
+    }
+}
+
+#[tauri::command]
+fn cancel_conversion(jobs: State<'_, JobRegistry>, job_id: &str) -> bool {
+    match jobs.get(job_id) {
+        Some(job) => {
+            log_info(&format!("İş iptal ediliyor: {}", job_id), "Conversion");
+            job.cancel();
+            true
+        }
+        None => {
+            log_warning(&format!("İptal edilecek iş bulunamadı: {}", job_id), "Conversion");
+            false
+        }
+    }
+}
+
+#[tauri::command]
+fn pause_conversion(jobs: State<'_, JobRegistry>, job_id: &str) -> bool {
+    let paused = jobs.get(job_id).map(|job| job.pause()).unwrap_or(false);
+    log_info(&format!("İş duraklatma ({}): {}", job_id, paused), "Conversion");
+    paused
+}
+
+#[tauri::command]
+fn resume_conversion(jobs: State<'_, JobRegistry>, job_id: &str) -> bool {
+    let resumed = jobs.get(job_id).map(|job| job.resume()).unwrap_or(false);
+    log_info(&format!("İş devam ettirme ({}): {}", job_id, resumed), "Conversion");
+    resumed
+}
+
+// Kuyruğa birden fazla video ekler, her birinin kendi kesme/crop/boyut ayarı var
+#[tauri::command]
+fn enqueue_jobs(app: AppHandle, queue: State<'_, JobQueue>, requests: Vec<ConversionRequest>) -> Vec<QueuedJob> {
+    let jobs: Vec<QueuedJob> = requests
+        .into_iter()
+        .map(|mut request| {
+            // Platform ayarı istek alanlarından önce gelir
+            if let Some(preset) = request.preset.clone() {
+                preset.apply(&mut request);
+            }
+            queue.enqueue(request)
+        })
+        .collect();
+    log_info(&format!("Kuyruğa {} iş eklendi", jobs.len()), "Queue");
+
+    emit_queue(&app);
+    start_queued_jobs(&app);
+    jobs
+}
+
+#[tauri::command]
+fn get_queue(queue: State<'_, JobQueue>) -> QueueSnapshot {
+    queue.snapshot()
+}
+
+// Kuyruktaki işin sırasını değiştirir, çalışan işler etkilenmez
+#[tauri::command]
+fn move_job(app: AppHandle, queue: State<'_, JobQueue>, job_id: &str, index: usize) -> bool {
+    let moved = queue.move_job(job_id, index);
+    if moved {
+        emit_queue(&app);
+    }
+    moved
+}
+
+// İşi kuyruktan kaldırır, çalışıyorsa önce iptal edilir
+#[tauri::command]
+fn remove_job(app: AppHandle, queue: State<'_, JobQueue>, jobs: State<'_, JobRegistry>, job_id: &str) -> bool {
+    if let Some(job) = jobs.get(job_id) {
+        log_info(&format!("Çalışan iş kaldırılıyor: {}", job_id), "Queue");
+        job.cancel();
+    }
+
+    let removed = queue.remove(job_id).is_some();
+    if removed {
+        emit_queue(&app);
+    }
+    removed
+}
+
+#[tauri::command]
+fn set_concurrency(app: AppHandle, queue: State<'_, JobQueue>, concurrency: usize) -> usize {
+    let concurrency = queue.set_concurrency(concurrency);
+    log_info(&format!("Aynı anda çalışacak iş sayısı: {}", concurrency), "Queue");
+
+    emit_queue(&app);
+    start_queued_jobs(&app);
+    concurrency
+}
+
+#[tauri::command]
+fn get_settings(settings: State<'_, SettingsStore>) -> Settings {
+    settings.get()
+}
+
+// Ayarları kaydeder, doğrulanmış halini döndürür
+#[tauri::command]
+fn update_settings(store: State<'_, SettingsStore>, settings: Settings) -> Settings {
+    let settings = store.update(settings);
+    set_log_level(settings.log_level);
+    log_info(&format!("Ayarlar güncellendi: {:?}", settings), "Settings");
+    settings
+}
+
+// Biten dönüştürmeler, en yenisi başta
+#[tauri::command]
+fn get_history(history: State<'_, History>) -> Vec<HistoryEntry> {
+    history.entries()
+}
+
+#[tauri::command]
+fn clear_history(history: State<'_, History>) {
+    log_info("Geçmiş temizlendi", "History");
+    history.clear();
+}
+
+// Hazır platform ayarları ve kullanıcının ekledikleri
+#[tauri::command]
+fn get_presets(presets: State<'_, PresetRegistry>) -> Vec<Preset> {
+    presets.all()
+}
+
+#[tauri::command]
+fn save_preset(presets: State<'_, PresetRegistry>, preset: Preset) -> Result<Preset, ConversionError> {
+    log_info(&format!("Platform ayarı kaydediliyor: {}", preset.id), "Presets");
+    presets.save(preset)
+}
+
+#[tauri::command]
+fn delete_preset(presets: State<'_, PresetRegistry>, id: &str) -> Result<bool, ConversionError> {
+    log_info(&format!("Platform ayarı siliniyor: {}", id), "Presets");
+    presets.delete(id)
+}
+
+// Json dosyasındaki ayarları ekler, aynı id'li olanları değiştirir
+#[tauri::command]
+fn import_presets(presets: State<'_, PresetRegistry>, path: &str) -> Result<Vec<Preset>, ConversionError> {
+    log_info(&format!("Platform ayarları içe aktarılıyor: {}", path), "Presets");
+    presets.import(Path::new(path)).map_err(|e| {
+        log_error(&format!("Platform ayarları okunamadı: {}", e), "Presets");
+        e
+    })
+}
+
+// `ids` verilmezse kullanıcının tüm ayarları yazılır
+#[tauri::command]
+fn export_presets(presets: State<'_, PresetRegistry>, path: &str, ids: Option<Vec<String>>) -> Result<usize, ConversionError> {
+    log_info(&format!("Platform ayarları dışa aktarılıyor: {}", path), "Presets");
+    presets.export(Path::new(path), ids)
+}
+
+fn emit_queue(app: &AppHandle) {
+    let _ = app.emit_all("queue_changed", app.state::<JobQueue>().snapshot());
+}
+
+/// Starts as many queued jobs as the concurrency allows, each on its own thread.
+/// A finished job starts the next one.
+fn start_queued_jobs(app: &AppHandle) {
+    for queued in app.state::<JobQueue>().start_next() {
+        log_info(&format!("Kuyruktaki iş başlatıldı: {}", queued.id), "Queue");
+        let _ = app.emit_all("job_state", &queued);
+
+        let job = app.state::<JobRegistry>().register(&queued.id);
+        let app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let job_id = queued.id.as_str();
+            let started_at = chrono::Local::now();
+            let mut details = ConversionDetails::default();
+            let result = run_conversion(&job, job_id, &queued.request, &mut details, |p: ConversionProgress| {
+                // Durum değiştiyse job_state, her ilerlemede job_progress gönderilir
+                if let Some(changed) = app.state::<JobQueue>().update(job_id, &p) {
+                    let _ = app.emit_all("job_state", &changed);
+                }
+                let _ = app.emit_all("job_progress", JobProgress { job_id, progress: &p });
+            });
+
+            app.state::<JobRegistry>().remove(job_id);
+            if let Err(e) = &result {
+                log_conversion_error(e);
+            }
+            app.state::<History>().add(HistoryEntry::new(job_id, &queued.request, &result, &details, started_at));
+
+            if let Some(finished) = app.state::<JobQueue>().finish(job_id, result) {
+                let _ = app.emit_all("job_state", &finished);
+            }
+            start_queued_jobs(&app);
+        });
+    }
+}
+
+/// The whole conversion of one request, shared by the editor and the command line.
+/// Progress goes to `on_progress`, the editor emits it as an event and the CLI prints it.
+/// What it is encoded with goes to `details` for the history, also when it fails.
+fn run_conversion(
+    job: &Arc<JobHandle>,
+    job_id: &str,
+    request: &ConversionRequest,
+    details: &mut ConversionDetails,
+    on_progress: impl FnMut(ConversionProgress) + Send
+) -> Result<String, ConversionError> {
+    let input = request.input.as_str();
+    let target_size = request.target_size;
+    let (start_time, end_time) = (request.start_time, request.end_time);
+    let crop = request.crop;
+
     log_info(&format!("Video dönüştürme başlatıldı: {}", input), "Conversion");
     log_info(&format!("Hedef boyut: {} MB", target_size), "Conversion");
+    if let Some(preset) = &request.preset {
+        log_info(&format!("Platform ayarı: {}", preset.name), "Conversion");
+    }
 
     // Zaman aralığı kontrolü
     if let Some(start) = start_time {
@@ -257,82 +601,376 @@ fn convert_video(
 
     // Crop değerlerini log
     if let Some(crop_val) = &crop {
-        log_info(&format!("Crop ayarları: {}", crop_val), "Conversion");
+        log_info(&format!("Crop ayarları: {:?}", crop_val), "Conversion");
 
         // Crop parametrelerini doğrula
-        if let Some(crop_obj) = crop_val.as_object() {
-            if let (Some(w), Some(h)) = (
-                crop_obj.get("width").and_then(|v| v.as_f64()),
-                crop_obj.get("height").and_then(|v| v.as_f64())
-            ) {
-                if w <= 0.0 || h <= 0.0 || w >= 100.0 || h >= 100.0 {
-                    log_warning(&format!("Geçersiz crop boyutları: genişlik={}, yükseklik={}", w, h), "Conversion");
-                }
-            }
+        let (w, h) = (crop_val.width, crop_val.height);
+        if w <= 0.0 || h <= 0.0 || w >= 100.0 || h >= 100.0 {
+            log_warning(&format!("Geçersiz crop boyutları: genişlik={}, yükseklik={}", w, h), "Conversion");
         }
     } else {
         log_info("Crop işlemi yapılmayacak", "Conversion");
     }
 
-    let output = get_output(input);
-    log_info(&format!("Çıktı dosyası: {}", output), "Conversion");
+    // İlerleme başlangıcı olarak %0 bildirimi, süre probe'dan sonra belli olur
+    let mut progress = ProgressReporter::new(0.0, on_progress);
+    progress.report(ConversionProgress::default());
 
-    let duration = get_duration(input);
-    log_info(&format!("Video süresi: {}", duration), "Conversion");
+    if let Some(format) = request.animation {
+        return run_animation(job, job_id, request, format, details, &mut progress);
+    }
+    if request.sticker {
+        return run_sticker(job, job_id, request, details, &mut progress);
+    }
+    if let Some(format) = request.audio_only {
+        return run_audio(job, job_id, request, format, details, &mut progress);
+    }
 
-    let audio_rate = get_original_audio_rate(input);
-    log_info(&format!("Ses bit hızı: {}", audio_rate), "Conversion");
+    // Codec'in encoder'ı paketlenen ffmpeg'de yoksa baştan hata ver
+    let codec = request.codec;
+    let encoder = codec
+        .find_encoder(&get_video_encoders()?)
+        .ok_or(ConversionError::CodecUnavailable { codec })?;
+    log_info(&format!("Codec: {:?} ({})", codec, encoder), "Conversion");
 
-    let min_size = get_target_size(audio_rate, duration);
+    // Her girdi için tek ffprobe çağrısı. Kesme, ses, bit hızı ve çözünürlük
+    // tahminle aynı hesaptan gelir
+    let (sources, plan) = probe_and_plan(request)?;
+    log_crop(request, &plan);
+    let media = &sources[0];
+    if !plan.clips.is_empty() {
+        log_info(&format!("{} video birleştirilecek", plan.clips.len()), "Conversion");
+    }
+    log_info(&format!("Video süresi: {}", media.duration), "Conversion");
+    log_info(&format!("Kaynak ses bit hızı: {}", media.audio_rate()), "Conversion");
+
+    let clip_duration = plan.clip_duration;
+    if let Some(video) = media.primary_video() {
+        let (width, height) = video.display_size();
+        log_info(&format!("Kaynak boyutu: {}x{} (döndürme: {})", width, height, video.rotation), "Conversion");
+    }
+    if plan.end_time != end_time {
+        log_info(&format!("Süre sınırı nedeniyle bitiş zamanı: {:?}", plan.end_time), "Conversion");
+    }
+    log_info(&format!("Kesilmiş süre: {}", clip_duration), "Conversion");
+    details.clip_duration = Some(clip_duration);
+    details.audio = plan.audio;
+    if let Some(format) = &plan.format {
+        details.width = Some(format.width);
+        details.height = Some(format.height);
+        details.fps = format.fps;
+    }
+
+    // Çıktı adı şablondan üretilir, dosya varsa çakışma politikası uygulanır
+    let output = get_output(request, codec.container().extension(), clip_duration)?;
+    log_info(&format!("Çıktı dosyası: {}", output.path), "Conversion");
+
+    // Ses codec'i, bit hızı ve kanal sayısı toplam bütçeye göre seçilir.
+    // Ses yoksa ya da kaldırılması istendiyse bütçenin tamamı videoya kalır
+    match &plan.audio {
+        Some(audio) => log_info(&format!("Ses ayarları: {:?} {}k, {} Hz, {} kanal", audio.codec, audio.bitrate, audio.sample_rate, audio.channels), "Conversion"),
+        None if request.strip_audio => log_info("Ses kaldırılacak, bütçenin tamamı videoya", "Conversion"),
+        None => log_info("Kaynakta ses yok", "Conversion"),
+    }
+
+    let min_size = plan.min_size;
     log_info(&format!("Minimum boyut: {}", min_size), "Conversion");
 
-    // İlerleme başlangıcı olarak %0 bildirimi
-    window.emit("conversion_progress", 0.0).unwrap();
+    progress.set_duration(clip_duration);
 
-    if !is_minsize(min_size, target_size) {
-        log_error(&format!("Hata: Minimum boyut ({}) hedef boyuttan ({}) büyük!", min_size, target_size), "Conversion");
-        println!("{min_size}");
-        return "".to_string();
+    // Tahminle aynı kontrol, editör "sığmaz" diyorsa dönüştürme de başlamaz
+    if let Err(e) = plan.check_target(target_size) {
+        log_error(&format!("Hata: Ses ve en düşük video bit hızı hedef boyuta ({}) sığmıyor!", target_size), "Conversion");
+        return Err(e);
     }
 
-    let target_bitrate = get_target_video_rate(target_size, duration, audio_rate);
+    let target_bitrate = plan.video_rate;
     log_info(&format!("Hedef video bit hızı: {}", target_bitrate), "Conversion");
 
-    // İlk geçiş
-    window.emit("conversion_progress", 25.0).unwrap();
-    log_info("İlk geçiş başlatıldı", "Conversion");
-    convert_first(input, target_bitrate, start_time, end_time);
-    log_info("İlk geçiş tamamlandı", "Conversion");
-
-    // İkinci geçiş
-    window.emit("conversion_progress", 50.0).unwrap();
-    log_info("İkinci geçiş başlatıldı", "Conversion");
-    convert_out(input, target_bitrate, audio_rate, &output, start_time, end_time, crop);
-    log_info("İkinci geçiş tamamlandı", "Conversion");
-
-    // Dosya kontrolü
-    let output_path = Path::new(&output);
-    if output_path.exists() {
-        let metadata = std::fs::metadata(&output);
-        if let Ok(meta) = metadata {
-            let file_size = meta.len();
-            log_info(&format!("Çıktı dosyası boyutu: {} bytes", file_size), "Conversion");
-
-            if file_size == 0 {
-                log_error("HATA: Çıktı dosyası 0 byte!", "Conversion");
-            }
-        } else {
-            log_error("Çıktı dosyası metadata okunamadı", "Conversion");
-        }
-    } else {
-        log_error("Çıktı dosyası oluşturulamadı", "Conversion");
+    if let Some(format) = &plan.format {
+        log_info(&format!("Çıktı formatı: {}x{}, fps: {:?}, bpp: {:.3}", format.width, format.height, format.fps, format.bits_per_pixel), "Conversion");
     }
 
+    // Passlog ve ara dosyalar işe özel geçici klasörde tutulur,
+    // fonksiyondan nasıl çıkılırsa çıkılsın klasör silinir
+    let workspace = Workspace::create(job_id)?;
+    log_info(&format!("Geçici klasör: {}", workspace.dir().display()), "Conversion");
+    let encoded = workspace.path(&format!("output.{}", codec.container().extension()));
+
+    // Kaynak zaten sığıyorsa yeniden encode edilmeden kopyalanır, kalite kaybı olmaz
+    let copied = if plan.stream_copy {
+        let copy = StreamCopy {
+            input: input.to_string(),
+            output: encoded.clone(),
+            codec,
+            start_time: plan.start_time,
+            end_time: plan.end_time,
+            audio: media.primary_audio().is_some() && !request.strip_audio,
+        };
+        try_stream_copy(&copy, target_size, &mut progress, job)?
+    } else {
+        None
+    };
+
+    let file_size = match copied {
+        Some(size) => size,
+        None => encode_to_target(request, &plan, encoder, &encoded, &workspace, &mut progress, job)?,
+    };
+    log_info(&format!("Çıktı dosyası boyutu: {} bytes", file_size), "Conversion");
+
+    // Yarım kalan dosya hiçbir zaman hedef klasöre yazılmaz, sadece bitince taşınır
+    let output = output.save(&encoded)?;
+
     // İşlem tamamlandı
-    window.emit("conversion_progress", 100.0).unwrap();
+    progress.report(ConversionProgress::done());
     log_info(&format!("Video dönüştürme tamamlandı, çıktı: {}", output), "Conversion");
 
-    return output;
+    Ok(output)
+}
+
+/// GIF or animated WebP export, frame rate, size and palette are lowered until it fits
+fn run_animation(
+    job: &Arc<JobHandle>,
+    job_id: &str,
+    request: &ConversionRequest,
+    format: AnimationFormat,
+    details: &mut ConversionDetails,
+    progress: &mut ProgressReporter,
+) -> Result<String, ConversionError> {
+    let encoder = format.find_encoder(&get_video_encoders()?)?;
+    log_info(&format!("Animasyon: {:?} ({})", format, encoder), "Conversion");
+
+    let (sources, plan) = probe_and_plan(request)?;
+    log_crop(request, &plan);
+    let video = sources[0].primary_video().ok_or(ConversionError::InvalidInput {
+        input: request.input.clone(),
+    })?;
+    let source_width = match plan.crop {
+        Some(crop) => crop.width,
+        None => video.display_size().0,
+    };
+
+    let output = get_output(request, format.extension(), plan.clip_duration)?;
+    log_info(&format!("Çıktı dosyası: {}", output.path), "Conversion");
+    progress.set_duration(plan.clip_duration);
+
+    let workspace = Workspace::create(job_id)?;
+    let encoded = workspace.path(&format!("output.{}", format.extension()));
+    let mut encode = AnimationEncode {
+        input: request.input.clone(),
+        output: encoded.clone(),
+        format,
+        encoder: encoder.to_string(),
+        start_time: plan.start_time,
+        end_time: plan.end_time,
+        crop: plan.crop,
+        sar_scale: plan.sar_scale,
+        settings: AnimationSettings::initial(source_width, video.fps),
+    };
+
+    let file_size = encode_animation_to_size(&mut encode, request.target_size, progress, job)?;
+    log_info(&format!("Animasyon boyutu: {} bytes, ayarlar: {:?}", file_size, encode.settings), "Conversion");
+    details.clip_duration = Some(plan.clip_duration);
+    details.width = Some(encode.settings.width);
+    details.fps = Some(encode.settings.fps);
+
+    let output = output.save(&encoded)?;
+    progress.report(ConversionProgress::done());
+    log_info(&format!("Animasyon tamamlandı, çıktı: {}", output), "Conversion");
+
+    Ok(output)
+}
+
+/// Telegram video sticker: VP9 WebM in a 512 px square, at most 3 seconds,
+/// 256 KB and 30 fps, without audio. The finished file is checked against the rules.
+fn run_sticker(
+    job: &Arc<JobHandle>,
+    job_id: &str,
+    request: &ConversionRequest,
+    details: &mut ConversionDetails,
+    progress: &mut ProgressReporter,
+) -> Result<String, ConversionError> {
+    let codec = VideoCodec::Vp9;
+    let encoder = codec
+        .find_encoder(&get_video_encoders()?)
+        .ok_or(ConversionError::CodecUnavailable { codec })?;
+    log_info(&format!("Sticker: {:?} ({})", codec, encoder), "Conversion");
+
+    let (sources, plan) = probe_and_plan(request)?;
+    log_crop(request, &plan);
+    let media = &sources[0];
+    let video = media.primary_video().ok_or(ConversionError::InvalidInput {
+        input: request.input.clone(),
+    })?;
+
+    // Sticker 3 saniyeden uzun olamaz, fazlası sondan kesilir
+    let end_time = get_sticker_end(plan.start_time, plan.end_time, plan.clip_duration);
+    let duration = get_clip_duration(media.duration, plan.start_time, end_time);
+    if end_time != plan.end_time {
+        log_info(&format!("Sticker süre sınırı nedeniyle bitiş zamanı: {:?}", end_time), "Conversion");
+    }
+
+    let limit = get_sticker_limit(request.target_size);
+    let output = get_output(request, codec.container().extension(), duration)?;
+    log_info(&format!("Çıktı dosyası: {}", output.path), "Conversion");
+    progress.set_duration(duration);
+
+    let workspace = Workspace::create(job_id)?;
+    let encoded = workspace.path("sticker.webm");
+    let mut encode = StickerEncode {
+        input: request.input.clone(),
+        output: encoded.clone(),
+        encoder: encoder.to_string(),
+        start_time: plan.start_time,
+        end_time,
+        crop: plan.crop,
+        sar_scale: plan.sar_scale,
+        fps: get_sticker_fps(video.fps),
+        video_bitrate: get_sticker_rate(limit, duration),
+        passlog_prefix: workspace.passlog_prefix(),
+    };
+    details.clip_duration = Some(duration);
+    details.width = Some(STICKER_SIDE);
+    details.height = Some(STICKER_SIDE);
+    details.fps = Some(encode.fps);
+
+    encode_sticker_to_size(&mut encode, limit, duration, progress, job)?;
+    // Boyut tutsa da süre, çözünürlük, fps ve ses kuralları dosyanın kendisinden kontrol edilir
+    let file_size = check_sticker(&encoded, limit)?;
+    log_info(&format!("Sticker boyutu: {} bytes, {}k", file_size, encode.video_bitrate), "Conversion");
+
+    let output = output.save(&encoded)?;
+    progress.report(ConversionProgress::done());
+    log_info(&format!("Sticker tamamlandı, çıktı: {}", output), "Conversion");
+
+    Ok(output)
+}
+
+/// Only the selected audio stream, trimmed and encoded with the bit rate that fills the target size
+fn run_audio(
+    job: &Arc<JobHandle>,
+    job_id: &str,
+    request: &ConversionRequest,
+    format: AudioFormat,
+    details: &mut ConversionDetails,
+    progress: &mut ProgressReporter,
+) -> Result<String, ConversionError> {
+    let (sources, plan) = probe_and_plan(request)?;
+    let media = &sources[0];
+    let stream = request.audio_stream;
+    let source = media
+        .audio
+        .get(stream as usize)
+        .ok_or(ConversionError::AudioStreamMissing { stream, count: media.audio.len() })?;
+    log_info(&format!("Ses akışı {}: {} ({:?})", stream, source.codec, source.language), "Conversion");
+
+    // Sadece baştan ve sondan kesilir, saklanacak parçalar birleştirilmez
+    let duration = get_clip_duration(media.duration, plan.start_time, plan.end_time);
+    let settings = get_audio_only_settings(format, request.target_size, duration, source)?;
+    match &settings {
+        Some(audio) => log_info(&format!("Ses ayarları: {:?} {}k, {} Hz, {} kanal", audio.codec, audio.bitrate, audio.sample_rate, audio.channels), "Conversion"),
+        None => log_info("FLAC kayıpsız, bit hızı seçilmez", "Conversion"),
+    }
+    details.clip_duration = Some(duration);
+    details.audio = settings;
+
+    let output = get_output(request, format.extension(), duration)?;
+    log_info(&format!("Çıktı dosyası: {}", output.path), "Conversion");
+    progress.set_duration(duration);
+
+    let workspace = Workspace::create(job_id)?;
+    let encoded = workspace.path(&format!("audio.{}", format.extension()));
+    let mut extract = AudioExtract {
+        input: request.input.clone(),
+        output: encoded.clone(),
+        format,
+        stream,
+        start_time: plan.start_time,
+        end_time: plan.end_time,
+        settings,
+    };
+
+    let file_size = encode_audio_to_size(&mut extract, request.target_size, progress, job)?;
+    log_info(&format!("Ses boyutu: {} bytes", file_size), "Conversion");
+
+    let output = output.save(&encoded)?;
+    progress.report(ConversionProgress::done());
+    log_info(&format!("Ses çıkarma tamamlandı, çıktı: {}", output), "Conversion");
+
+    Ok(output)
+}
+
+/// Remuxes the source, `None` when the copy came out too big or ffmpeg
+/// could not copy it and the video has to be encoded after all
+fn try_stream_copy(
+    copy: &StreamCopy,
+    target_size: f32,
+    progress: &mut ProgressReporter,
+    job: &JobHandle,
+) -> Result<Option<u64>, ConversionError> {
+    log_info("Kaynak hedefe sığıyor, yeniden encode edilmeden kopyalanıyor", "Conversion");
+
+    match copy.run(progress, job) {
+        Ok(size) if size <= get_target_bytes(target_size) => Ok(Some(size)),
+        Ok(size) => {
+            log_info(&format!("Kopya {} bytes, hedefi aşıyor, encode edilecek", size), "Conversion");
+            Ok(None)
+        }
+        Err(ConversionError::Cancelled) => Err(ConversionError::Cancelled),
+        Err(e) => {
+            log_warning(&format!("Kopyalama başarısız, encode edilecek: {}", e), "Conversion");
+            Ok(None)
+        }
+    }
+}
+
+/// Two-pass encode of the plan, repeated with a corrected bit rate until it fits
+fn encode_to_target(
+    request: &ConversionRequest,
+    plan: &ConversionPlan,
+    encoder: &str,
+    encoded: &str,
+    workspace: &Workspace,
+    progress: &mut ProgressReporter,
+    job: &JobHandle,
+) -> Result<u64, ConversionError> {
+    // İki geçişli encode, ilk geçiş tek sefer çalışır
+    let mut encode = TwoPassEncode {
+        input: request.input.clone(),
+        output: encoded.to_string(),
+        codec: request.codec,
+        encoder: encoder.to_string(),
+        video_bitrate: plan.video_rate,
+        audio: plan.audio,
+        start_time: plan.start_time,
+        end_time: plan.end_time,
+        ranges: plan.ranges.clone(),
+        clips: plan.clips.clone(),
+        crop: plan.crop,
+        sar_scale: plan.sar_scale,
+        format: plan.format,
+        passlog_prefix: workspace.passlog_prefix(),
+    };
+
+    // Çıktı hedef boyutu aşarsa düzeltilmiş bit hızıyla tekrar encode edilir
+    log_info("İki geçişli encode başlatıldı", "Conversion");
+    encode_to_size(&mut encode, request.target_size, plan.clip_duration, progress, job)
+}
+
+// Paketlenen ffmpeg'in desteklediği codec'ler, editördeki seçim listesi için
+#[tauri::command(async)]
+fn get_available_codecs() -> Result<Vec<VideoCodec>, ConversionError> {
+    codec::get_available_codecs()
+}
+
+// Editörün video, ses ve altyazı bilgilerini göstermesi için
+#[tauri::command(async)]
+fn probe_media(input: &str) -> Result<MediaInfo, ConversionError> {
+    log_info(&format!("Medya bilgisi okunuyor: {}", input), "Probe");
+    probe::probe_media(input).map_err(|e| {
+        log_error(&format!("Medya bilgisi okunamadı: {}", e), "Probe");
+        e
+    })
 }
 
 // F12 tuşuna basıldığında DevTools açmak/kapatmak için
@@ -349,12 +987,52 @@ fn toggle_devtools(window: tauri::Window) {
 }
 
 fn main() {
+    let settings = SettingsStore::load();
+    set_log_level(settings.get().log_level);
+
     log_info("Uygulama başlatıldı", "System");
     log_environment();
 
+    // Çöken çalıştırmalardan kalan geçici klasörleri temizle
+    let removed = sweep_stale_workspaces();
+    if removed > 0 {
+        log_info(&format!("{} eski geçici klasör silindi", removed), "System");
+    }
+
+    // Video yolu verildiyse pencere açmadan komut satırından dönüştür
+    let context = tauri::generate_context!();
+    let presets = PresetRegistry::load();
+    if let Some(code) = cli::run_from_args(context.config(), context.package_info(), &settings.get(), &presets) {
+        log_info(&format!("Komut satırı modu bitti, çıkış kodu: {}", code), "System");
+        std::process::exit(code);
+    }
+
     tauri::Builder::default()
+        .manage(JobRegistry::default())
+        .manage(settings)
+        .manage(presets)
         .invoke_handler(tauri::generate_handler![
             convert_video,
+            estimate_conversion,
+            cancel_conversion,
+            pause_conversion,
+            resume_conversion,
+            enqueue_jobs,
+            get_queue,
+            move_job,
+            remove_job,
+            set_concurrency,
+            get_history,
+            clear_history,
+            get_settings,
+            update_settings,
+            get_presets,
+            save_preset,
+            delete_preset,
+            import_presets,
+            export_presets,
+            probe_media,
+            get_available_codecs,
             log_to_file_js,
             open_file_explorer,
             check_file_exists,
@@ -362,23 +1040,24 @@ fn main() {
             toggle_devtools
         ])
         .setup(|app| {
-            match app.get_cli_matches() {
-                Ok(_matches) => {
-                    log_info("CLI parametreleri alındı", "System");
-                    println!("got matches");
-                }
-                Err(_) => {
-                    log_info("CLI parametresi yok", "System");
-                    println!("no matches");
-                }
-            };
+            // Kuyruk ve geçmiş uygulama veri klasöründe tutulur
+            let data_dir = app
+                .path_resolver()
+                .app_data_dir()
+                .unwrap_or_else(|| env::temp_dir().join("Max8VideoEditor"));
+            log_info(&format!("Veri klasörü: {}", data_dir.display()), "System");
+            app.manage(JobQueue::load(data_dir.join("queue.json")));
+            app.manage(History::load(data_dir.join("history.json")));
+
+            // Uygulama kapanırken bekleyen işler kaldığı yerden devam eder
+            start_queued_jobs(&app.handle());
 
             // Build modunda da DevTools'u etkinleştir
             log_info("DevTools erişimi etkinleştirildi", "System");
 
             Ok(())
         })
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 
     log_info("Uygulama kapatıldı", "System");
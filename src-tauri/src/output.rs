@@ -0,0 +1,338 @@
+use directories::UserDirs;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::codec::VideoCodec;
+use crate::ffmpeg::{io_error, ConversionError, ConversionRequest};
+use crate::workspace::move_file;
+
+/// Gives the same `<stem>-8m.mp4` names as before templates existed
+pub const DEFAULT_TEMPLATE: &str = "{stem}-{size}m.{ext}";
+
+/// Auto-increment gives up after this many taken names
+const MAX_INCREMENT: u32 = 9999;
+
+/// Where finished videos are written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum OutputLocation {
+    /// next to the source video
+    SameFolder,
+    /// always the same folder, `None` is the user's Videos folder
+    Fixed { dir: Option<String> },
+    /// the frontend asks for a folder every time and sends it as `output_dir`
+    Ask,
+}
+
+/// What happens when the output file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    /// `name-1.mp4`, `name-2.mp4`, ...
+    AutoIncrement,
+    Overwrite,
+    /// the conversion fails with `OutputExists`, before encoding when the file is already there
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputPolicy {
+    pub location: OutputLocation,
+    /// file name with `{stem}`, `{size}`, `{codec}`, `{ext}`, `{date}`, `{time}` and `{duration}` tokens
+    pub template: String,
+    pub collision: CollisionPolicy,
+}
+
+impl Default for OutputPolicy {
+    fn default() -> Self {
+        OutputPolicy {
+            location: OutputLocation::Fixed { dir: None },
+            template: DEFAULT_TEMPLATE.to_string(),
+            collision: CollisionPolicy::AutoIncrement,
+        }
+    }
+}
+
+/// Values the tokens of a file name template are replaced with
+pub struct TemplateValues<'a> {
+    pub stem: &'a str,
+    /// target size in MB
+    pub size: f32,
+    pub codec: VideoCodec,
+    pub ext: &'a str,
+    /// length of the clip in seconds
+    pub duration: f32,
+}
+
+/// Fills in the template. The extension is added when the template has no `{ext}`,
+/// characters that are not allowed in file names are replaced.
+pub fn render_template(template: &str, values: &TemplateValues) -> String {
+    let now = chrono::Local::now();
+    let template = if template.trim().is_empty() {
+        DEFAULT_TEMPLATE
+    } else {
+        template
+    };
+
+    let mut name = template
+        .replace("{stem}", values.stem)
+        .replace("{size}", &values.size.to_string())
+        .replace("{codec}", values.codec.name())
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string())
+        .replace("{duration}", &format!("{}s", values.duration.round() as u64));
+
+    if !name.contains("{ext}") {
+        name.push_str(".{ext}");
+    }
+    let name = name.replace("{ext}", values.ext);
+
+    // Şablon klasör oluşturamaz, yol ayraçları da dahil geçersiz karakterler değiştirilir
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Output file of a conversion. `path` is the name picked when planning, other jobs can
+/// take it before this one finishes so `save` applies the collision policy again.
+pub struct Output {
+    pub path: String,
+    /// rendered template, before any `-1` suffix
+    name: PathBuf,
+    input: PathBuf,
+    collision: CollisionPolicy,
+}
+
+impl Output {
+    /// Moves the finished file to its final name, returns the path it ended up at.
+    /// The name is claimed by creating it exclusively, so two jobs never get the same one.
+    pub fn save(&self, from: &str) -> Result<String, ConversionError> {
+        if let Some(parent_dir) = self.name.parent() {
+            fs::create_dir_all(parent_dir).map_err(|e| io_error(parent_dir, e))?;
+        }
+
+        let (path, claimed) = self.claim()?;
+        let to = path.to_string_lossy().to_string();
+        if let Err(e) = move_file(from, &to) {
+            // Boş bırakılan yer tutucu dosya silinir
+            if claimed {
+                let _ = fs::remove_file(&path);
+            }
+            return Err(e);
+        }
+
+        Ok(to)
+    }
+
+    /// Path the file is moved to and whether an empty placeholder was created there
+    fn claim(&self) -> Result<(PathBuf, bool), ConversionError> {
+        if create_new(&self.name)? {
+            return Ok((self.name.clone(), true));
+        }
+
+        match self.collision {
+            // Kaynak dosyanın kendisinin üzerine asla yazılmaz
+            CollisionPolicy::Overwrite if !same_file(&self.name, &self.input) => {
+                Ok((self.name.clone(), false))
+            }
+            CollisionPolicy::Skip => Err(ConversionError::OutputExists {
+                path: self.name.to_string_lossy().to_string(),
+            }),
+            _ => {
+                for n in 1..=MAX_INCREMENT {
+                    let candidate = incremented(&self.name, n);
+                    if create_new(&candidate)? {
+                        return Ok((candidate, true));
+                    }
+                }
+                Err(ConversionError::OutputExists {
+                    path: self.name.to_string_lossy().to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Output path of a conversion following its output policy. Collisions are resolved
+/// here so a skipped file fails before encoding, and again by `Output::save`.
+pub fn get_output(
+    request: &ConversionRequest,
+    extension: &str,
+    clip_duration: f32,
+) -> Result<Output, ConversionError> {
+    let input = Path::new(&request.input);
+    let stem = match input.file_stem() {
+        Some(stem) => stem.to_string_lossy(),
+        None => {
+            return Err(ConversionError::InvalidInput {
+                input: request.input.clone(),
+            })
+        }
+    };
+
+    let name = render_template(
+        &request.output.template,
+        &TemplateValues {
+            stem: &stem,
+            size: request.target_size,
+            codec: request.codec,
+            ext: extension,
+            duration: clip_duration,
+        },
+    );
+    let name = output_dir(request, input).join(name);
+    let path = resolve_collision(name.clone(), input, request.output.collision)?;
+
+    Ok(Output {
+        path: path.to_string_lossy().to_string(),
+        name,
+        input: input.to_path_buf(),
+        collision: request.output.collision,
+    })
+}
+
+fn output_dir(request: &ConversionRequest, input: &Path) -> PathBuf {
+    if let Some(dir) = &request.output_dir {
+        return PathBuf::from(dir);
+    }
+
+    match &request.output.location {
+        OutputLocation::SameFolder => input
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from(".")),
+        OutputLocation::Fixed { dir: Some(dir) } => PathBuf::from(dir),
+        // Klasör sorulmadıysa (kuyruk, komut satırı) Videolar klasörü kullanılır
+        OutputLocation::Fixed { dir: None } | OutputLocation::Ask => videos_dir(input),
+    }
+}
+
+/// if video dir fails, use the parent dir of the clip, and then the current dir
+fn videos_dir(input: &Path) -> PathBuf {
+    UserDirs::new()
+        .and_then(|user_dirs| user_dirs.video_dir().map(Path::to_path_buf))
+        .or_else(|| input.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn resolve_collision(
+    path: PathBuf,
+    input: &Path,
+    collision: CollisionPolicy,
+) -> Result<PathBuf, ConversionError> {
+    if !path.exists() {
+        return Ok(path);
+    }
+
+    match collision {
+        // Kaynak dosyanın kendisinin üzerine asla yazılmaz
+        CollisionPolicy::Overwrite if !same_file(&path, input) => Ok(path),
+        CollisionPolicy::Skip => Err(ConversionError::OutputExists {
+            path: path.to_string_lossy().to_string(),
+        }),
+        _ => (1..=MAX_INCREMENT)
+            .map(|n| incremented(&path, n))
+            .find(|candidate| !candidate.exists())
+            .ok_or(ConversionError::OutputExists {
+                path: path.to_string_lossy().to_string(),
+            }),
+    }
+}
+
+/// `name.mp4` to `name-n.mp4`
+fn incremented(path: &Path, n: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    path.with_file_name(format!("{}-{}{}", stem, n, ext))
+}
+
+/// Creates an empty file only if nothing is there yet, false when the name is taken
+fn create_new(path: &Path) -> Result<bool, ConversionError> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(io_error(path, e)),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> TemplateValues<'static> {
+        TemplateValues {
+            stem: "clip",
+            size: 8.0,
+            codec: VideoCodec::H265,
+            ext: "mp4",
+            duration: 12.6,
+        }
+    }
+
+    #[test]
+    fn empty_template_uses_the_default() {
+        assert_eq!(render_template("", &values()), "clip-8m.mp4");
+        assert_eq!(render_template("  ", &values()), "clip-8m.mp4");
+    }
+
+    #[test]
+    fn template_fills_in_every_token() {
+        let name = render_template("{stem}_{size}_{codec}_{duration}.{ext}", &values());
+        assert_eq!(name, "clip_8_h265_13s.mp4");
+
+        let mut values = values();
+        values.size = 9.5;
+        assert_eq!(
+            render_template("{ext}-{stem}-{size}", &values),
+            "mp4-clip-9.5"
+        );
+    }
+
+    #[test]
+    fn template_without_ext_gets_the_extension() {
+        assert_eq!(render_template("{stem}-small", &values()), "clip-small.mp4");
+    }
+
+    #[test]
+    fn unknown_tokens_are_kept() {
+        assert_eq!(render_template("{stem}-{foo}", &values()), "clip-{foo}.mp4");
+        assert_eq!(render_template("{stem}-{size", &values()), "clip-{size.mp4");
+    }
+
+    #[test]
+    fn template_cannot_create_folders() {
+        assert_eq!(render_template("../{stem}", &values()), ".._clip.mp4");
+        assert_eq!(render_template("{stem}\\a:b?", &values()), "clip_a_b_.mp4");
+    }
+
+    #[test]
+    fn incremented_keeps_the_extension() {
+        let path = Path::new("videos").join("clip-8m.mp4");
+        assert_eq!(
+            incremented(&path, 2),
+            Path::new("videos").join("clip-8m-2.mp4")
+        );
+        assert_eq!(incremented(Path::new("clip"), 1), Path::new("clip-1"));
+    }
+}
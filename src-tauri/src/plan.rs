@@ -0,0 +1,585 @@
+use serde::Serialize;
+
+use crate::codec::{AudioCodec, VideoCodec};
+use crate::ffmpeg::{
+    get_audio_settings, get_clip_duration, get_kept_ranges, get_output_format, get_pixel_crop,
+    get_sar_scale, get_target_bytes, get_target_size, get_target_video_rate, get_total_rate,
+    is_minsize, limit_ranges, min_bits_per_pixel, AudioSettings, ConversionError,
+    ConversionRequest, MergeClip, OutputFormat, PixelCrop, TimeRange,
+};
+use crate::probe::{self, MediaInfo};
+
+/// Below this video bit rate (kib/s) even the smallest output size is a blur,
+/// the estimate calls the target infeasible
+pub const MIN_VIDEO_RATE: f32 = 64.0;
+
+/// A stream copy is only tried when the estimated copy leaves this much room,
+/// the keyframe aligned cut can start up to a GOP before `start_time`
+const COPY_SAFETY: f64 = 0.90;
+
+/// Everything decided before encoding, shared by the conversion and the estimate
+#[derive(Debug, Clone)]
+pub struct ConversionPlan {
+    pub start_time: Option<f32>,
+    /// already cut to the preset's length limit
+    pub end_time: Option<f32>,
+    /// two or more parts to join, a single kept range becomes `start_time` and `end_time`
+    pub ranges: Vec<TimeRange>,
+    /// inputs of a merge, empty for a single input
+    pub clips: Vec<MergeClip>,
+    /// seconds that actually get encoded, all kept ranges together
+    pub clip_duration: f32,
+    /// `None` when the source has no audio or it is removed
+    pub audio: Option<AudioSettings>,
+    /// kib/s
+    pub video_rate: f32,
+    /// MB the audio alone takes
+    pub min_size: f32,
+    pub crop: Option<PixelCrop>,
+    pub sar_scale: Option<(u32, u32)>,
+    /// `None` when the source has no video
+    pub format: Option<OutputFormat>,
+    /// the source can be remuxed with `-c copy` instead of encoded
+    pub stream_copy: bool,
+}
+
+impl ConversionPlan {
+    pub fn audio_rate(&self) -> f32 {
+        self.audio.map(|audio| audio.bitrate).unwrap_or(0.0)
+    }
+
+    /// Lowest total bit rate worth encoding in kib/s, the audio and `MIN_VIDEO_RATE` for the video
+    pub fn needed_rate(&self) -> f32 {
+        self.audio_rate() + if self.format.is_some() { MIN_VIDEO_RATE } else { 0.0 }
+    }
+
+    /// Fails when the target leaves no room for the audio and `MIN_VIDEO_RATE` of video.
+    /// The estimate and the conversion both decide with this, a stream copy always fits.
+    pub fn check_target(&self, target_size: f32) -> Result<(), ConversionError> {
+        let fits = is_minsize(self.min_size, target_size)
+            && (self.format.is_none() || self.video_rate >= MIN_VIDEO_RATE);
+        if self.stream_copy || fits {
+            return Ok(());
+        }
+
+        Err(ConversionError::TargetTooSmall {
+            min_size: get_needed_size(self.needed_rate(), self.clip_duration).max(self.min_size),
+            target_size,
+        })
+    }
+}
+
+/// Probes every input of the request and plans it, the first media is the one
+/// the output is named after
+pub fn probe_and_plan(
+    request: &ConversionRequest,
+) -> Result<(Vec<MediaInfo>, ConversionPlan), ConversionError> {
+    if request.clips.is_empty() {
+        let media = probe::probe_media(&request.input)?;
+        let plan = plan_conversion(request, &media)?;
+        return Ok((vec![media], plan));
+    }
+
+    let media = request
+        .clips
+        .iter()
+        .map(|clip| probe::probe_media(&clip.input))
+        .collect::<Result<Vec<_>, _>>()?;
+    let plan = plan_merge(request, &media)?;
+    Ok((media, plan))
+}
+
+/// Plans joining several inputs. The output takes the frame size and frame rate
+/// of the first clip, the others are letterboxed into it.
+pub fn plan_merge(
+    request: &ConversionRequest,
+    media: &[MediaInfo],
+) -> Result<ConversionPlan, ConversionError> {
+    let mut clips = Vec::new();
+    for (clip, media) in request.clips.iter().zip(media) {
+        if media.primary_video().is_none() {
+            return Err(ConversionError::InvalidInput {
+                input: clip.input.clone(),
+            });
+        }
+        let duration = get_clip_duration(media.duration, clip.start_time, clip.end_time);
+        if duration <= 0.0 {
+            return Err(ConversionError::InvalidDuration {
+                value: duration.to_string(),
+            });
+        }
+
+        clips.push(MergeClip {
+            input: clip.input.clone(),
+            start: clip.start_time.unwrap_or(0.0).max(0.0),
+            duration,
+            audio: media.primary_audio().is_some(),
+        });
+    }
+
+    // Platformun süre sınırı son videolardan kesilir, tek videodaki parçalarla aynı şekilde
+    let preset = request.preset.as_ref();
+    if let Some(max_duration) = preset.and_then(|preset| preset.max_duration) {
+        let ranges: Vec<TimeRange> = clips
+            .iter()
+            .map(|clip| TimeRange {
+                start: clip.start,
+                end: clip.start + clip.duration,
+            })
+            .collect();
+        let limited = limit_ranges(&ranges, max_duration);
+
+        clips.truncate(limited.len());
+        for (clip, range) in clips.iter_mut().zip(limited) {
+            clip.duration = range.duration();
+        }
+    }
+    let clip_duration: f32 = clips.iter().map(|clip| clip.duration).sum();
+
+    // Ses ilk sesli videoya göre ayarlanır, sesi olmayanlara sessizlik eklenir
+    let audio = match media.iter().find_map(MediaInfo::primary_audio) {
+        Some(source) if !request.strip_audio => {
+            let mut audio = get_audio_settings(
+                get_total_rate(request.target_size, clip_duration),
+                request.codec.container(),
+                request.audio_codec,
+                Some(source),
+            );
+            if let Some(max_rate) = preset.and_then(|preset| preset.max_audio_bitrate) {
+                audio.bitrate = audio.bitrate.min(max_rate);
+            }
+            Some(audio)
+        }
+        _ => None,
+    };
+    let audio_rate = audio.map(|audio| audio.bitrate).unwrap_or(0.0);
+    let video_rate = get_target_video_rate(request.target_size, clip_duration, audio_rate);
+
+    // Birleştirmede fps her zaman sabitlenir, videolar farklı hızlarda olabilir
+    let first = media.first().and_then(MediaInfo::primary_video);
+    let format = first.map(|video| {
+        let source_fps = video.fps.filter(|fps| *fps > 0.0).unwrap_or(30.0);
+        let mut format = get_output_format(
+            video_rate,
+            video.display_size(),
+            Some(source_fps),
+            request.codec,
+            preset.and_then(|preset| preset.max_height),
+        );
+        format.fps = Some(format.fps.unwrap_or(source_fps));
+        format
+    });
+
+    Ok(ConversionPlan {
+        start_time: None,
+        end_time: None,
+        ranges: Vec::new(),
+        clips,
+        clip_duration,
+        audio,
+        video_rate,
+        min_size: get_target_size(audio_rate, clip_duration),
+        crop: None,
+        sar_scale: None,
+        format,
+        stream_copy: false,
+    })
+}
+
+/// Works out trim, audio, bit rate and output size of a request for a probed input
+pub fn plan_conversion(
+    request: &ConversionRequest,
+    media: &MediaInfo,
+) -> Result<ConversionPlan, ConversionError> {
+    let preset = request.preset.as_ref();
+    let max_duration = preset.and_then(|preset| preset.max_duration);
+    let mut start_time = request.start_time;
+    let mut end_time = request.end_time;
+
+    // Birden fazla parça varsa süre sınırı son parçalardan kesilir
+    let mut ranges = get_kept_ranges(media.duration, &request.ranges);
+    if !request.ranges.is_empty() && ranges.is_empty() {
+        return Err(ConversionError::InvalidDuration {
+            value: "0".to_string(),
+        });
+    }
+    if let Some(max_duration) = max_duration {
+        ranges = limit_ranges(&ranges, max_duration);
+    }
+    // Tek parça sıradan bir kesme gibi işlenir, kopyalama da mümkün olur
+    if ranges.len() == 1 {
+        start_time = Some(ranges[0].start);
+        end_time = Some(ranges[0].end);
+        ranges.clear();
+    }
+
+    // Platformun süre sınırını aşan kısım sondan kesilir
+    if let Some(max_duration) = max_duration.filter(|_| ranges.is_empty()) {
+        if get_clip_duration(media.duration, start_time, end_time) > max_duration {
+            end_time = Some(start_time.unwrap_or(0.0).max(0.0) + max_duration);
+        }
+    }
+
+    let clip_duration = if ranges.is_empty() {
+        get_clip_duration(media.duration, start_time, end_time)
+    } else {
+        ranges.iter().map(TimeRange::duration).sum()
+    };
+    if clip_duration <= 0.0 {
+        return Err(ConversionError::InvalidDuration {
+            value: clip_duration.to_string(),
+        });
+    }
+
+    let container = request.codec.container();
+    let audio = match media.primary_audio() {
+        Some(source) if !request.strip_audio => {
+            let mut audio = get_audio_settings(
+                get_total_rate(request.target_size, clip_duration),
+                container,
+                request.audio_codec,
+                Some(source),
+            );
+            if let Some(max_rate) = preset.and_then(|preset| preset.max_audio_bitrate) {
+                audio.bitrate = audio.bitrate.min(max_rate);
+            }
+            Some(audio)
+        }
+        _ => None,
+    };
+    let audio_rate = audio.map(|audio| audio.bitrate).unwrap_or(0.0);
+    let video_rate = get_target_video_rate(request.target_size, clip_duration, audio_rate);
+
+    // Crop yüzdeleri kaynak videonun gerçek (döndürülmüş) boyutuna göre piksele çevrilir
+    let video = media.primary_video();
+    let crop = match (&request.crop, video) {
+        (Some(crop), Some(video)) => get_pixel_crop(crop, video),
+        _ => None,
+    };
+
+    // Çözünürlük ve fps bit hızı bütçesine göre seçilir, kaynak büyütülmez
+    let format = video.map(|video| {
+        let source = match crop {
+            Some(crop) => (crop.width, crop.height),
+            None => video.display_size(),
+        };
+        get_output_format(
+            video_rate,
+            source,
+            video.fps,
+            request.codec,
+            preset.and_then(|preset| preset.max_height),
+        )
+    });
+
+    // Parçalar birleştirilirken yeniden encode edilmeleri gerekir
+    let stream_copy = ranges.is_empty() && can_stream_copy(request, media, clip_duration);
+
+    Ok(ConversionPlan {
+        start_time,
+        end_time,
+        ranges,
+        clips: Vec::new(),
+        clip_duration,
+        audio,
+        video_rate,
+        min_size: get_target_size(audio_rate, clip_duration),
+        crop,
+        sar_scale: video.and_then(get_sar_scale),
+        format,
+        stream_copy,
+    })
+}
+
+/// True when the source is already in the asked codec, nothing needs cropping
+/// or scaling down and the trimmed part fits the target as it is
+fn can_stream_copy(request: &ConversionRequest, media: &MediaInfo, clip_duration: f32) -> bool {
+    let video = match media.primary_video() {
+        Some(video) => video,
+        None => return false,
+    };
+    if request.crop.is_some() || VideoCodec::from_stream(&video.codec) != Some(request.codec) {
+        return false;
+    }
+
+    // 10 bit ve 4:4:4 videolar tarayıcılarda ve telefonlarda oynatılamıyor
+    if let Some(pix_fmt) = video.pix_fmt.as_deref() {
+        if pix_fmt != "yuv420p" && pix_fmt != "yuvj420p" {
+            return false;
+        }
+    }
+
+    let (width, height) = video.display_size();
+    if let Some(max_height) = request.preset.as_ref().and_then(|preset| preset.max_height) {
+        if width.min(height) > max_height {
+            return false;
+        }
+    }
+
+    // Ses de olduğu gibi kopyalanır, kapsayıcıya uymuyorsa ya da başka codec istendiyse encode gerekir
+    if let Some(audio) = media.primary_audio().filter(|_| !request.strip_audio) {
+        let codec = match AudioCodec::from_stream(&audio.codec) {
+            Some(codec) => codec,
+            None => return false,
+        };
+        if !request.codec.container().supports_audio(codec)
+            || request.audio_codec.map_or(false, |requested| requested != codec)
+        {
+            return false;
+        }
+    }
+
+    match get_copy_bytes(media, clip_duration, request.strip_audio) {
+        Some(bytes) => bytes <= get_target_bytes(request.target_size) as f64 * COPY_SAFETY,
+        None => false,
+    }
+}
+
+/// Size of the trimmed part copied as it is, from the overall bit rate of the source
+fn get_copy_bytes(media: &MediaInfo, clip_duration: f32, strip_audio: bool) -> Option<f64> {
+    let bit_rate = media.bit_rate.map(|rate| rate as f64).or_else(|| {
+        media
+            .size
+            .filter(|_| media.duration > 0.0)
+            .map(|size| size as f64 * 8.0 / media.duration as f64)
+    })?;
+    let audio_rate = match media.primary_audio().and_then(|audio| audio.bit_rate) {
+        Some(rate) if strip_audio => rate as f64,
+        _ => 0.0,
+    };
+
+    Some((bit_rate - audio_rate).max(0.0) * clip_duration as f64 / 8.0)
+}
+
+/// How the output will look, from the bits each pixel gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityTier {
+    /// visibly blocky, the target is too small for the clip
+    Low,
+    Medium,
+    High,
+    /// the size limit barely matters
+    Excellent,
+}
+
+impl QualityTier {
+    /// `ratio` is bits per pixel over the codec's minimum
+    fn from_ratio(ratio: f64) -> Self {
+        if ratio < 1.0 {
+            QualityTier::Low
+        } else if ratio < 2.0 {
+            QualityTier::Medium
+        } else if ratio < 4.0 {
+            QualityTier::High
+        } else {
+            QualityTier::Excellent
+        }
+    }
+}
+
+/// What a conversion would produce, returned by `estimate_conversion`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Estimate {
+    /// seconds
+    pub clip_duration: f32,
+    /// kib/s
+    pub video_rate: f32,
+    pub audio: Option<AudioSettings>,
+    /// `None` when the source has no video
+    pub format: Option<OutputFormat>,
+    pub quality: QualityTier,
+    /// MB the audio alone takes
+    pub min_size: f32,
+    pub feasible: bool,
+    /// seconds, longest clip that fits the target, only set when it is infeasible
+    pub max_duration: Option<f32>,
+    /// the source fits already and gets copied without re-encoding
+    pub stream_copy: bool,
+}
+
+impl Estimate {
+    pub fn new(plan: &ConversionPlan, request: &ConversionRequest) -> Self {
+        let feasible = plan.check_target(request.target_size).is_ok();
+
+        let quality = match plan.format {
+            // Kopyalanan video kaynakla aynı kalitede
+            Some(_) if plan.stream_copy => QualityTier::Excellent,
+            Some(format) if feasible => QualityTier::from_ratio(
+                format.bits_per_pixel / min_bits_per_pixel(request.codec),
+            ),
+            Some(_) => QualityTier::Low,
+            // Sadece ses varsa kalite ses bit hızına göre
+            None => match plan.audio {
+                Some(audio) if audio.bitrate >= audio.codec.max_bitrate() => QualityTier::Excellent,
+                Some(audio) if audio.bitrate > audio.codec.min_bitrate() => QualityTier::High,
+                _ => QualityTier::Low,
+            },
+        };
+
+        // Ses kendi tabanında kalır, video en az MIN_VIDEO_RATE alacak kadar kısaltılır
+        let max_duration = if feasible {
+            None
+        } else {
+            Some(get_max_duration(request.target_size, plan.needed_rate()))
+        };
+
+        Estimate {
+            clip_duration: plan.clip_duration,
+            video_rate: plan.video_rate,
+            audio: plan.audio,
+            format: plan.format,
+            quality,
+            min_size: plan.min_size,
+            feasible,
+            max_duration,
+            stream_copy: plan.stream_copy,
+        }
+    }
+}
+
+/// Seconds of output that fit `size` MB at `rate` kib/s, the inverse of `get_total_rate`
+pub fn get_max_duration(size: f32, rate: f32) -> f32 {
+    if rate <= 0.0 {
+        return 0.0;
+    }
+    ((size * 8192.00) / (1.048576 * rate)).floor()
+}
+
+/// MB that `duration` seconds take at `rate` kib/s, the inverse of `get_total_rate`
+pub fn get_needed_size(rate: f32, duration: f32) -> f32 {
+    (rate * 1.048576 * duration) / 8192.00
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Container;
+
+    fn request(target_size: f32) -> ConversionRequest {
+        serde_json::from_value(serde_json::json!({
+            "input": "clip.mp4",
+            "targetSize": target_size,
+        }))
+        .unwrap()
+    }
+
+    /// Plans a 1080p 30 fps clip the way `plan_conversion` does, without probing
+    fn plan(target_size: f32, clip_duration: f32, video: bool) -> ConversionPlan {
+        let total_rate = get_total_rate(target_size, clip_duration);
+        let audio = get_audio_settings(total_rate, Container::Mp4, None, None);
+        let video_rate = get_target_video_rate(target_size, clip_duration, audio.bitrate);
+        let format = if video {
+            Some(get_output_format(
+                video_rate,
+                (1920, 1080),
+                Some(30.0),
+                VideoCodec::H264,
+                None,
+            ))
+        } else {
+            None
+        };
+
+        ConversionPlan {
+            start_time: None,
+            end_time: None,
+            ranges: Vec::new(),
+            clips: Vec::new(),
+            clip_duration,
+            audio: Some(audio),
+            video_rate,
+            min_size: get_target_size(audio.bitrate, clip_duration),
+            crop: None,
+            sar_scale: None,
+            format,
+            stream_copy: false,
+        }
+    }
+
+    #[test]
+    fn quality_tier_boundaries() {
+        assert_eq!(QualityTier::from_ratio(0.0), QualityTier::Low);
+        assert_eq!(QualityTier::from_ratio(0.99), QualityTier::Low);
+        assert_eq!(QualityTier::from_ratio(1.0), QualityTier::Medium);
+        assert_eq!(QualityTier::from_ratio(1.99), QualityTier::Medium);
+        assert_eq!(QualityTier::from_ratio(2.0), QualityTier::High);
+        assert_eq!(QualityTier::from_ratio(3.99), QualityTier::High);
+        assert_eq!(QualityTier::from_ratio(4.0), QualityTier::Excellent);
+    }
+
+    #[test]
+    fn audio_takes_its_share_of_the_budget() {
+        // 8 MB / 60 s yaklaşık 1042 kib/s, sesin payı onda biri
+        let estimate = Estimate::new(&plan(8.0, 60.0, true), &request(8.0));
+        let audio = estimate.audio.unwrap();
+        assert_eq!((audio.codec, audio.bitrate), (AudioCodec::Aac, 104.0));
+        assert!((estimate.video_rate + audio.bitrate - get_total_rate(8.0, 60.0)).abs() < 0.01);
+
+        // Pay codec'in tavanını ve tabanını aşamaz
+        let audio = plan(100.0, 60.0, true).audio.unwrap();
+        assert_eq!(audio.bitrate, AudioCodec::Aac.max_bitrate());
+        let audio = plan(1.0, 60.0, true).audio.unwrap();
+        assert_eq!(
+            (audio.bitrate, audio.channels),
+            (AudioCodec::Aac.min_bitrate(), 1)
+        );
+    }
+
+    #[test]
+    fn video_below_the_minimum_rate_is_infeasible() {
+        // 60 saniyede ses 32k tabanında, video 0.8 MB'de 64k'yı geçer, 0.7 MB'de geçemez
+        let fits = plan(0.8, 60.0, true);
+        assert!(fits.video_rate >= MIN_VIDEO_RATE);
+        assert!(fits.check_target(0.8).is_ok());
+        let estimate = Estimate::new(&fits, &request(0.8));
+        assert!(estimate.feasible);
+        assert_eq!(estimate.max_duration, None);
+
+        let too_small = plan(0.7, 60.0, true);
+        assert!(too_small.video_rate > 0.0 && too_small.video_rate < MIN_VIDEO_RATE);
+        match too_small.check_target(0.7) {
+            Err(ConversionError::TargetTooSmall {
+                min_size,
+                target_size,
+            }) => assert!(min_size > target_size),
+            other => panic!("expected TargetTooSmall, got {:?}", other),
+        }
+        let estimate = Estimate::new(&too_small, &request(0.7));
+        assert!(!estimate.feasible);
+        assert_eq!(estimate.quality, QualityTier::Low);
+    }
+
+    #[test]
+    fn stream_copy_is_always_feasible() {
+        let mut copy = plan(0.7, 60.0, true);
+        copy.stream_copy = true;
+        assert!(copy.check_target(0.7).is_ok());
+        assert_eq!(
+            Estimate::new(&copy, &request(0.7)).quality,
+            QualityTier::Excellent
+        );
+    }
+
+    #[test]
+    fn max_duration_leaves_room_for_audio_and_minimum_video() {
+        // Ses 32k + video 64k = 96 kib/s, 0.7 MB'ye 56 saniye sığar
+        let estimate = Estimate::new(&plan(0.7, 60.0, true), &request(0.7));
+        assert_eq!(estimate.max_duration, Some(56.0));
+        assert!(plan(0.7, 56.0, true).check_target(0.7).is_ok());
+
+        // Sadece ses varsa video payı ayrılmaz
+        let audio_only = plan(0.1, 60.0, false);
+        let estimate = Estimate::new(&audio_only, &request(0.1));
+        assert!(!estimate.feasible);
+        assert_eq!(estimate.max_duration, Some(24.0));
+    }
+
+    #[test]
+    fn max_duration_is_the_inverse_of_the_total_rate() {
+        assert_eq!(get_max_duration(8.0, 0.0), 0.0);
+        assert_eq!(get_max_duration(8.0, 1024.0), 61.0);
+        assert!((get_needed_size(get_total_rate(8.0, 60.0), 60.0) - 8.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::codec::{AudioCodec, VideoCodec};
+use crate::ffmpeg::{io_error, ConversionError, ConversionRequest};
+use crate::settings::config_dir;
+use crate::store::{read_json, write_json};
+
+/// Upload limits and format rules of a platform
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub id: String,
+    pub name: String,
+    /// MB, the largest file the platform accepts
+    pub max_size: f32,
+    /// short side in pixels, 720 for 1280x720
+    #[serde(default)]
+    pub max_height: Option<u32>,
+    /// seconds, longer clips are cut at the end
+    #[serde(default)]
+    pub max_duration: Option<f32>,
+    /// `None` keeps the codec the user picked
+    #[serde(default)]
+    pub codec: Option<VideoCodec>,
+    /// `None` keeps the audio codec the user picked
+    #[serde(default)]
+    pub audio_codec: Option<AudioCodec>,
+    #[serde(default)]
+    pub strip_audio: bool,
+    /// kib/s
+    #[serde(default)]
+    pub max_audio_bitrate: Option<f32>,
+    /// built-in presets can't be changed or deleted, never read from a file
+    #[serde(default, skip_deserializing)]
+    pub builtin: bool,
+}
+
+impl Preset {
+    fn builtin(id: &str, name: &str, max_size: f32) -> Self {
+        Preset {
+            id: id.to_string(),
+            name: name.to_string(),
+            max_size,
+            max_height: None,
+            max_duration: None,
+            codec: None,
+            audio_codec: None,
+            strip_audio: false,
+            max_audio_bitrate: None,
+            builtin: true,
+        }
+    }
+
+    /// For platforms that only play H.264 with AAC
+    fn h264(self) -> Self {
+        Preset {
+            codec: Some(VideoCodec::H264),
+            audio_codec: Some(AudioCodec::Aac),
+            ..self
+        }
+    }
+
+    /// Applies the size, codec and audio rules. A smaller target than the platform
+    /// limit is kept, codecs the preset leaves open keep the user's choice. Duration
+    /// and resolution limits need the probe, the conversion reads them from `request.preset`.
+    pub fn apply(&self, request: &mut ConversionRequest) {
+        request.target_size = if request.target_size > 0.0 {
+            request.target_size.min(self.max_size)
+        } else {
+            self.max_size
+        };
+        if let Some(codec) = self.codec {
+            request.codec = codec;
+        }
+        if let Some(audio_codec) = self.audio_codec {
+            request.audio_codec = Some(audio_codec);
+        }
+        request.strip_audio = request.strip_audio || self.strip_audio;
+        request.preset = Some(self.clone());
+    }
+
+    fn validate(&self) -> Result<(), ConversionError> {
+        let invalid = |message: String| Err(ConversionError::InvalidPreset { message });
+
+        if self.id.trim().is_empty() || self.name.trim().is_empty() {
+            return invalid("a preset needs an id and a name".to_string());
+        }
+        if !self.max_size.is_finite() || self.max_size <= 0.0 {
+            return invalid(format!("{}: maxSize must be above 0", self.id));
+        }
+        if self.max_duration.map_or(false, |duration| duration <= 0.0) {
+            return invalid(format!("{}: maxDuration must be above 0", self.id));
+        }
+        let unsupported = match (self.codec, self.audio_codec) {
+            (Some(codec), Some(audio_codec)) => !codec.container().supports_audio(audio_codec),
+            _ => false,
+        };
+        if unsupported {
+            return invalid(format!(
+                "{}: {:?} audio can't be used with {:?}",
+                self.id, self.audio_codec, self.codec
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Presets that ship with the app. Sizes are the platform limits in MB of 10^6 bytes,
+/// platforms that count in MiB get that difference as headroom. Platforms whose
+/// players only take H.264 and AAC set those codecs, the others keep the user's.
+pub fn builtin_presets() -> Vec<Preset> {
+    vec![
+        Preset::builtin("discord", "Discord", 10.0),
+        Preset::builtin("discord-nitro-basic", "Discord Nitro Basic", 50.0),
+        Preset::builtin("discord-nitro", "Discord Nitro", 500.0),
+        Preset {
+            max_height: Some(720),
+            ..Preset::builtin("whatsapp", "WhatsApp", 16.0).h264()
+        },
+        Preset {
+            max_height: Some(1080),
+            ..Preset::builtin("telegram", "Telegram", 2000.0).h264()
+        },
+        // 25 MB sınırı var ama ek dosyalar base64 ile yaklaşık üçte bir büyür
+        Preset {
+            max_height: Some(720),
+            ..Preset::builtin("email", "Email (Gmail, Outlook)", 18.0).h264()
+        },
+        Preset {
+            max_height: Some(1080),
+            max_duration: Some(140.0),
+            max_audio_bitrate: Some(128.0),
+            ..Preset::builtin("twitter", "Twitter / X", 512.0).h264()
+        },
+    ]
+}
+
+/// Built-in presets plus the ones the user imported, which are kept in `presets.json`
+pub struct PresetRegistry {
+    user: Mutex<Vec<Preset>>,
+    path: PathBuf,
+}
+
+impl PresetRegistry {
+    pub fn load() -> Self {
+        let path = config_dir().join("presets.json");
+        let user = read_json(&path).unwrap_or_default();
+        PresetRegistry {
+            user: Mutex::new(user),
+            path,
+        }
+    }
+
+    pub fn all(&self) -> Vec<Preset> {
+        let mut presets = builtin_presets();
+        presets.extend(self.user.lock().unwrap().iter().cloned());
+        presets
+    }
+
+    pub fn get(&self, id: &str) -> Result<Preset, ConversionError> {
+        self.all()
+            .into_iter()
+            .find(|preset| preset.id == id)
+            .ok_or(ConversionError::InvalidPreset {
+                message: format!("unknown preset {}", id),
+            })
+    }
+
+    /// Adds a user preset or replaces the one with the same id
+    pub fn save(&self, preset: Preset) -> Result<Preset, ConversionError> {
+        self.save_all(vec![preset]).map(|mut saved| saved.remove(0))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool, ConversionError> {
+        let mut user = self.user.lock().unwrap();
+        let count = user.len();
+        user.retain(|preset| preset.id != id);
+        if user.len() == count {
+            return Ok(false);
+        }
+
+        self.write(&user)?;
+        Ok(true)
+    }
+
+    /// Reads presets from a json file with a single preset or a list of them
+    pub fn import(&self, file: &Path) -> Result<Vec<Preset>, ConversionError> {
+        let text = fs::read_to_string(file).map_err(|e| io_error(file, e))?;
+        let invalid = |e: serde_json::Error| ConversionError::InvalidPreset {
+            message: format!("{}: {}", file.display(), e),
+        };
+
+        let presets = match serde_json::from_str::<Value>(&text).map_err(invalid)? {
+            Value::Array(values) => values
+                .into_iter()
+                .map(serde_json::from_value)
+                .collect::<Result<Vec<Preset>, _>>()
+                .map_err(invalid)?,
+            value => vec![serde_json::from_value(value).map_err(invalid)?],
+        };
+
+        self.save_all(presets)
+    }
+
+    /// Writes the user presets, or only the given ones, to a json file
+    pub fn export(&self, file: &Path, ids: Option<Vec<String>>) -> Result<usize, ConversionError> {
+        let presets: Vec<Preset> = self
+            .all()
+            .into_iter()
+            .filter(|preset| match &ids {
+                Some(ids) => ids.contains(&preset.id),
+                None => !preset.builtin,
+            })
+            .collect();
+
+        write_json(file, &presets).map_err(|e| io_error(file, e))?;
+        Ok(presets.len())
+    }
+
+    fn save_all(&self, presets: Vec<Preset>) -> Result<Vec<Preset>, ConversionError> {
+        let builtin = builtin_presets();
+        for preset in presets.iter() {
+            preset.validate()?;
+            if builtin.iter().any(|b| b.id == preset.id) {
+                return Err(ConversionError::InvalidPreset {
+                    message: format!("{} is a built-in preset", preset.id),
+                });
+            }
+        }
+
+        let mut user = self.user.lock().unwrap();
+        for preset in presets.iter() {
+            match user.iter_mut().find(|p| p.id == preset.id) {
+                Some(existing) => *existing = preset.clone(),
+                None => user.push(preset.clone()),
+            }
+        }
+
+        self.write(&user)?;
+        Ok(presets)
+    }
+
+    fn write(&self, user: &[Preset]) -> Result<(), ConversionError> {
+        write_json(&self.path, &user).map_err(|e| io_error(&self.path, e))
+    }
+}
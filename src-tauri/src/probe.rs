@@ -0,0 +1,481 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::ffmpeg::{run, sidecar, stderr_tail, ConversionError};
+
+/// Everything the conversion needs to know about an input file,
+/// read with a single `ffprobe -show_format -show_streams` call
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaInfo {
+    pub format_name: String,
+    /// seconds
+    pub duration: f32,
+    /// bytes
+    pub size: Option<u64>,
+    /// bits/s of the whole file
+    pub bit_rate: Option<u64>,
+    pub video: Vec<VideoStream>,
+    pub audio: Vec<AudioStream>,
+    pub subtitles: Vec<SubtitleStream>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoStream {
+    pub index: u32,
+    pub codec: String,
+    pub profile: Option<String>,
+    /// coded size, before rotation
+    pub width: u32,
+    pub height: u32,
+    /// degrees, from the rotate tag or the display matrix
+    pub rotation: i32,
+    /// sample aspect ratio as "num:den", `None` for square pixels
+    pub sample_aspect_ratio: Option<String>,
+    pub fps: Option<f32>,
+    pub pix_fmt: Option<String>,
+    pub color_space: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_range: Option<String>,
+    /// bits/s
+    pub bit_rate: Option<u64>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioStream {
+    pub index: u32,
+    pub codec: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    /// bits/s
+    pub bit_rate: Option<u64>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubtitleStream {
+    pub index: u32,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+impl MediaInfo {
+    /// The stream ffmpeg picks by default, the first one
+    pub fn primary_video(&self) -> Option<&VideoStream> {
+        self.video.first()
+    }
+
+    pub fn primary_audio(&self) -> Option<&AudioStream> {
+        self.audio.first()
+    }
+
+    /// Audio bit rate of the first audio stream in kib/s, 0 when there is none or it's unknown
+    pub fn audio_rate(&self) -> f32 {
+        self.primary_audio()
+            .and_then(|audio| audio.bit_rate)
+            .map(|rate| rate as f32 / 1024.00)
+            .unwrap_or(0.00)
+    }
+}
+
+impl VideoStream {
+    /// Frame size as it is displayed: rotation applied and non-square pixels stretched
+    pub fn display_size(&self) -> (u32, u32) {
+        let mut width = self.width as f64;
+        let height = self.height as f64;
+
+        if let Some((num, den)) = self.sample_aspect_ratio.as_deref().and_then(parse_ratio) {
+            width = width * num / den;
+        }
+
+        let (width, height) = (width.round() as u32, height.round() as u32);
+        if self.rotation.rem_euclid(180) == 90 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+pub fn probe_media(input: &str) -> Result<MediaInfo, ConversionError> {
+    let command = sidecar("ffprobe")?.args([
+        "-v",
+        "error",
+        "-print_format",
+        "json",
+        "-show_format",
+        "-show_streams",
+        input,
+    ]);
+    let output = run("ffprobe", command)?;
+
+    if !output.status.success() {
+        return Err(ConversionError::ProbeFailed {
+            input: input.to_string(),
+            stderr: stderr_tail(&output.stderr),
+        });
+    }
+
+    let json: Value = serde_json::from_str(&output.stdout).map_err(|e| {
+        ConversionError::ProbeFailed {
+            input: input.to_string(),
+            stderr: e.to_string(),
+        }
+    })?;
+
+    parse_media_info(&json)
+}
+
+fn parse_media_info(json: &Value) -> Result<MediaInfo, ConversionError> {
+    let format = &json["format"];
+    let streams = json["streams"].as_array().cloned().unwrap_or_default();
+
+    let mut info = MediaInfo {
+        format_name: str_field(format, "format_name").unwrap_or_default(),
+        duration: 0.0,
+        size: num_field(format, "size").map(|size| size as u64),
+        bit_rate: num_field(format, "bit_rate").map(|rate| rate as u64),
+        video: Vec::new(),
+        audio: Vec::new(),
+        subtitles: Vec::new(),
+    };
+
+    // Container süresi yoksa (bazı webm/mkv dosyaları) en uzun stream süresini kullan
+    let mut longest_stream: Option<f64> = None;
+    for stream in streams.iter() {
+        if let Some(stream_duration) = num_field(stream, "duration") {
+            longest_stream = Some(
+                longest_stream.map_or(stream_duration, |longest| longest.max(stream_duration)),
+            );
+        }
+
+        match stream["codec_type"].as_str() {
+            // Kapak resimleri de video stream olarak görünür, onları atla
+            Some("video") if stream["disposition"]["attached_pic"].as_i64() != Some(1) => {
+                info.video.push(parse_video(stream))
+            }
+            Some("audio") => info.audio.push(parse_audio(stream)),
+            Some("subtitle") => info.subtitles.push(SubtitleStream {
+                index: index(stream),
+                codec: codec(stream),
+                language: str_field(&stream["tags"], "language"),
+                title: str_field(&stream["tags"], "title"),
+            }),
+            _ => {}
+        }
+    }
+
+    info.duration = match num_field(format, "duration").or(longest_stream) {
+        Some(duration) if duration.is_finite() && duration > 0.0 => duration as f32,
+        _ => {
+            return Err(ConversionError::InvalidDuration {
+                value: str_field(format, "duration").unwrap_or_else(|| "N/A".to_string()),
+            })
+        }
+    };
+
+    Ok(info)
+}
+
+fn parse_video(stream: &Value) -> VideoStream {
+    let fps = str_field(stream, "avg_frame_rate")
+        .as_deref()
+        .and_then(parse_ratio)
+        .or_else(|| {
+            str_field(stream, "r_frame_rate")
+                .as_deref()
+                .and_then(parse_ratio)
+        })
+        .map(|(num, den)| (num / den) as f32);
+
+    // "1:1" ve "0:1" (bilinmiyor) kare piksel demek
+    let sample_aspect_ratio = str_field(stream, "sample_aspect_ratio")
+        .filter(|sar| sar != "1:1" && parse_ratio(sar).is_some());
+
+    VideoStream {
+        index: index(stream),
+        codec: codec(stream),
+        profile: str_field(stream, "profile"),
+        width: num_field(stream, "width").unwrap_or(0.0) as u32,
+        height: num_field(stream, "height").unwrap_or(0.0) as u32,
+        rotation: rotation(stream),
+        sample_aspect_ratio,
+        fps,
+        pix_fmt: str_field(stream, "pix_fmt"),
+        color_space: str_field(stream, "color_space"),
+        color_transfer: str_field(stream, "color_transfer"),
+        color_primaries: str_field(stream, "color_primaries"),
+        color_range: str_field(stream, "color_range"),
+        bit_rate: num_field(stream, "bit_rate").map(|rate| rate as u64),
+        language: str_field(&stream["tags"], "language"),
+    }
+}
+
+fn parse_audio(stream: &Value) -> AudioStream {
+    AudioStream {
+        index: index(stream),
+        codec: codec(stream),
+        sample_rate: num_field(stream, "sample_rate").map(|rate| rate as u32),
+        channels: num_field(stream, "channels").map(|channels| channels as u32),
+        channel_layout: str_field(stream, "channel_layout"),
+        bit_rate: num_field(stream, "bit_rate").map(|rate| rate as u64),
+        language: str_field(&stream["tags"], "language"),
+    }
+}
+
+/// Phones store rotation either as a `rotate` tag (old ffmpeg) or in the display matrix side data
+fn rotation(stream: &Value) -> i32 {
+    if let Some(rotate) = num_field(&stream["tags"], "rotate") {
+        return rotate as i32;
+    }
+
+    stream["side_data_list"]
+        .as_array()
+        .and_then(|list| list.iter().find_map(|data| num_field(data, "rotation")))
+        // Display matrix saat yönünün tersine döndürmeyi negatif yazar, rotate tag'i ile aynı yöne çevir
+        .map(|rotation| (-rotation as i32).rem_euclid(360))
+        .unwrap_or(0)
+}
+
+fn index(stream: &Value) -> u32 {
+    num_field(stream, "index").unwrap_or(0.0) as u32
+}
+
+fn codec(stream: &Value) -> String {
+    str_field(stream, "codec_name").unwrap_or_else(|| "unknown".to_string())
+}
+
+/// ffprobe writes "N/A" or leaves fields out when it doesn't know them
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    match &value[key] {
+        Value::String(s) if !s.is_empty() && s != "N/A" && s != "unknown" => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Numbers come as strings in the json output ("bit_rate": "128000")
+fn num_field(value: &Value, key: &str) -> Option<f64> {
+    match &value[key] {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses "30000/1001" or "16:9", `None` for "0/0" and friends
+fn parse_ratio(ratio: &str) -> Option<(f64, f64)> {
+    let (num, den) = ratio.split_once(['/', ':'])?;
+    let num: f64 = num.trim().parse().ok()?;
+    let den: f64 = den.trim().parse().ok()?;
+
+    if num > 0.0 && den > 0.0 {
+        Some((num, den))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn video_stream(extra: Value) -> Value {
+        let mut stream = json!({
+            "index": 0,
+            "codec_name": "h264",
+            "codec_type": "video",
+            "width": 1920,
+            "height": 1080,
+            "avg_frame_rate": "30000/1001",
+            "sample_aspect_ratio": "1:1",
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            stream[key] = value.clone();
+        }
+        stream
+    }
+
+    fn audio_stream() -> Value {
+        json!({
+            "index": 1,
+            "codec_name": "aac",
+            "codec_type": "audio",
+            "sample_rate": "48000",
+            "channels": 2,
+            "bit_rate": "128000",
+            "duration": "12.500000",
+        })
+    }
+
+    fn media(format: Value, streams: Vec<Value>) -> Result<MediaInfo, ConversionError> {
+        parse_media_info(&json!({ "format": format, "streams": streams }))
+    }
+
+    #[test]
+    fn parses_format_and_streams() {
+        let info = media(
+            json!({
+                "format_name": "mov,mp4",
+                "duration": "12.345",
+                "size": "1000000",
+                "bit_rate": "648000",
+            }),
+            vec![video_stream(json!({ "bit_rate": "512000" })), audio_stream()],
+        )
+        .unwrap();
+
+        assert_eq!(info.format_name, "mov,mp4");
+        assert!((info.duration - 12.345).abs() < 0.001);
+        assert_eq!((info.size, info.bit_rate), (Some(1_000_000), Some(648_000)));
+
+        let video = info.primary_video().unwrap();
+        assert_eq!((video.width, video.height, video.rotation), (1920, 1080, 0));
+        assert_eq!(video.sample_aspect_ratio, None);
+        assert!((video.fps.unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(video.bit_rate, Some(512_000));
+
+        let audio = info.primary_audio().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (Some(48000), Some(2)));
+        assert_eq!(info.audio_rate(), 125.0);
+    }
+
+    #[test]
+    fn not_available_fields_are_none() {
+        let info = media(
+            json!({
+                "format_name": "matroska,webm",
+                "duration": "N/A",
+                "size": "N/A",
+                "bit_rate": "N/A",
+            }),
+            vec![
+                video_stream(json!({
+                    "bit_rate": "N/A",
+                    "avg_frame_rate": "0/0",
+                    "r_frame_rate": "25/1",
+                    "duration": "8.0",
+                })),
+                json!({
+                    "index": 1,
+                    "codec_name": "opus",
+                    "codec_type": "audio",
+                    "bit_rate": "N/A",
+                    "tags": { "language": "N/A" },
+                }),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!((info.size, info.bit_rate), (None, None));
+        assert_eq!(info.duration, 8.0);
+
+        let video = info.primary_video().unwrap();
+        assert_eq!(video.bit_rate, None);
+        assert_eq!(video.fps, Some(25.0));
+
+        let audio = info.primary_audio().unwrap();
+        assert_eq!((audio.bit_rate, audio.language.as_deref()), (None, None));
+        assert_eq!(info.audio_rate(), 0.0);
+    }
+
+    #[test]
+    fn duration_falls_back_to_the_longest_stream() {
+        let info = media(
+            json!({ "format_name": "matroska,webm" }),
+            vec![video_stream(json!({ "duration": "10.0" })), audio_stream()],
+        )
+        .unwrap();
+        assert_eq!(info.duration, 12.5);
+
+        // Container süresi varsa stream süreleri kullanılmaz
+        let info = media(
+            json!({ "format_name": "mov,mp4", "duration": "11.0" }),
+            vec![video_stream(json!({ "duration": "10.0" })), audio_stream()],
+        )
+        .unwrap();
+        assert_eq!(info.duration, 11.0);
+    }
+
+    #[test]
+    fn missing_duration_is_an_error() {
+        let result = media(
+            json!({ "format_name": "image2", "duration": "N/A" }),
+            vec![video_stream(json!({}))],
+        );
+        match result {
+            Err(ConversionError::InvalidDuration { value }) => assert_eq!(value, "N/A"),
+            other => panic!("expected InvalidDuration, got {:?}", other),
+        }
+
+        let result = media(
+            json!({ "duration": "0.000000" }),
+            vec![video_stream(json!({}))],
+        );
+        assert!(matches!(
+            result,
+            Err(ConversionError::InvalidDuration { .. })
+        ));
+    }
+
+    #[test]
+    fn rotation_from_tag_and_display_matrix() {
+        let rotated = |extra: Value| parse_video(&video_stream(extra));
+
+        let video = rotated(json!({ "tags": { "rotate": "90" } }));
+        assert_eq!(video.rotation, 90);
+        assert_eq!(video.display_size(), (1080, 1920));
+
+        // Display matrix saat yönünün tersini negatif yazar
+        let video = rotated(json!({ "side_data_list": [{ "rotation": -90 }] }));
+        assert_eq!(video.rotation, 90);
+        assert_eq!(video.display_size(), (1080, 1920));
+
+        let video = rotated(json!({ "side_data_list": [{ "rotation": 90 }] }));
+        assert_eq!(video.rotation, 270);
+        assert_eq!(video.display_size(), (1080, 1920));
+
+        let video = rotated(json!({ "side_data_list": [{ "rotation": 180 }] }));
+        assert_eq!(video.rotation, 180);
+        assert_eq!(video.display_size(), (1920, 1080));
+    }
+
+    #[test]
+    fn display_size_stretches_non_square_pixels() {
+        let video = parse_video(&video_stream(json!({
+            "width": 1440,
+            "sample_aspect_ratio": "4:3",
+            "tags": { "rotate": "270" },
+        })));
+        assert_eq!(video.sample_aspect_ratio.as_deref(), Some("4:3"));
+        assert_eq!(video.display_size(), (1080, 1920));
+
+        let video = parse_video(&video_stream(json!({ "sample_aspect_ratio": "0:1" })));
+        assert_eq!(video.sample_aspect_ratio, None);
+    }
+
+    #[test]
+    fn cover_art_is_not_a_video_stream() {
+        let cover = json!({
+            "index": 2,
+            "codec_name": "mjpeg",
+            "codec_type": "video",
+            "width": 600,
+            "height": 600,
+            "disposition": { "attached_pic": 1 },
+        });
+        let info = media(
+            json!({ "format_name": "mp3", "duration": "180.0" }),
+            vec![audio_stream(), cover],
+        )
+        .unwrap();
+
+        assert!(info.video.is_empty());
+        assert!(info.primary_video().is_none());
+        assert_eq!(info.audio.len(), 1);
+    }
+}
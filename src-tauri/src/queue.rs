@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::ffmpeg::{ConversionError, ConversionProgress, ConversionRequest, ConversionStage};
+use crate::jobs::new_job_id;
+use crate::store::{read_json, write_json};
+
+/// Upper limit for concurrent conversions, every ffmpeg process already uses all cores
+pub const MAX_CONCURRENCY: usize = 4;
+
+/// State of a job in the queue, emitted with the `job_state` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Probing,
+    Pass1,
+    Pass2,
+    Verifying,
+    Copying,
+    Encoding,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self,
+            JobState::Probing
+                | JobState::Pass1
+                | JobState::Pass2
+                | JobState::Verifying
+                | JobState::Copying
+                | JobState::Encoding
+        )
+    }
+}
+
+impl From<ConversionStage> for JobState {
+    fn from(stage: ConversionStage) -> Self {
+        match stage {
+            ConversionStage::Probing => JobState::Probing,
+            ConversionStage::Pass1 => JobState::Pass1,
+            ConversionStage::Pass2 => JobState::Pass2,
+            ConversionStage::Verifying => JobState::Verifying,
+            ConversionStage::Copying => JobState::Copying,
+            ConversionStage::Encoding => JobState::Encoding,
+            ConversionStage::Done => JobState::Done,
+        }
+    }
+}
+
+/// A conversion waiting in, running from or finished in the queue
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedJob {
+    pub id: String,
+    pub request: ConversionRequest,
+    pub state: JobState,
+    /// overall progress in percent
+    pub percent: f32,
+    /// output path once the job is done
+    pub output: Option<String>,
+    pub error: Option<ConversionError>,
+}
+
+/// Progress of a queued job, emitted with the `job_progress` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobProgress<'a> {
+    pub job_id: &'a str,
+    pub progress: &'a ConversionProgress,
+}
+
+/// The whole queue, emitted with the `queue_changed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueSnapshot {
+    pub jobs: Vec<QueuedJob>,
+    pub concurrency: usize,
+}
+
+/// What is written to `queue.json`, only the jobs that still have to run.
+/// Jobs that were running when the app closed start over.
+#[derive(Serialize, Deserialize)]
+struct SavedQueue {
+    jobs: Vec<SavedJob>,
+    concurrency: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedJob {
+    id: String,
+    request: ConversionRequest,
+}
+
+struct QueueState {
+    jobs: Vec<QueuedJob>,
+    concurrency: usize,
+    /// jobs handed out by `start_next` that haven't finished, removed ones included
+    running: usize,
+    next_id: u64,
+}
+
+/// Conversions the editor queued up, kept in the Tauri state. The queue only keeps the
+/// order and the state of the jobs, running them is up to the caller of `start_next`.
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    /// `None` keeps the queue in memory only
+    path: Option<PathBuf>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        JobQueue {
+            state: Mutex::new(QueueState {
+                jobs: Vec::new(),
+                concurrency: 1,
+                running: 0,
+                next_id: 0,
+            }),
+            path: None,
+        }
+    }
+}
+
+impl JobQueue {
+    /// Restores the jobs that were waiting when the app was closed
+    pub fn load(path: PathBuf) -> Self {
+        let queue = JobQueue {
+            path: Some(path),
+            ..Default::default()
+        };
+
+        if let Some(saved) = queue.path.as_deref().and_then(read_json::<SavedQueue>) {
+            let mut state = queue.state.lock().unwrap();
+            state.concurrency = saved.concurrency.clamp(1, MAX_CONCURRENCY);
+            state.jobs = saved
+                .jobs
+                .into_iter()
+                .map(|saved| QueuedJob {
+                    id: saved.id,
+                    request: saved.request,
+                    state: JobState::Queued,
+                    percent: 0.0,
+                    output: None,
+                    error: None,
+                })
+                .collect();
+        }
+
+        queue
+    }
+
+    /// Writes the unfinished jobs to disk, called after every change of the queue
+    fn save(&self, state: &QueueState) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let saved = SavedQueue {
+            jobs: state
+                .jobs
+                .iter()
+                .filter(|job| job.state == JobState::Queued || job.state.is_running())
+                .map(|job| SavedJob {
+                    id: job.id.clone(),
+                    request: job.request.clone(),
+                })
+                .collect(),
+            concurrency: state.concurrency,
+        };
+
+        if let Err(e) = write_json(path, &saved) {
+            eprintln!("Kuyruk kaydedilemedi: {} ({})", path.display(), e);
+        }
+    }
+
+    /// Adds a job at the end of the queue
+    pub fn enqueue(&self, request: ConversionRequest) -> QueuedJob {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+
+        // Aynı milisaniyede eklenen işler çakışmasın diye sıra numarası eklenir
+        let job = QueuedJob {
+            id: format!("{}-{}", new_job_id(), state.next_id),
+            request,
+            state: JobState::Queued,
+            percent: 0.0,
+            output: None,
+            error: None,
+        };
+        state.jobs.push(job.clone());
+        self.save(&state);
+        job
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        let state = self.state.lock().unwrap();
+        QueueSnapshot {
+            jobs: state.jobs.clone(),
+            concurrency: state.concurrency,
+        }
+    }
+
+    /// Sets how many jobs run at the same time, returns the value that was applied
+    pub fn set_concurrency(&self, concurrency: usize) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.concurrency = concurrency.clamp(1, MAX_CONCURRENCY);
+        self.save(&state);
+        state.concurrency
+    }
+
+    /// Marks the first queued jobs as started, as many as the concurrency allows
+    pub fn start_next(&self) -> Vec<QueuedJob> {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let mut started = Vec::new();
+
+        while state.running < state.concurrency {
+            let job = match state.jobs.iter_mut().find(|job| job.state == JobState::Queued) {
+                Some(job) => job,
+                None => break,
+            };
+
+            job.state = JobState::Probing;
+            started.push(job.clone());
+            state.running += 1;
+        }
+
+        started
+    }
+
+    /// Stores the progress of a running job, returns the job when its state changed
+    pub fn update(&self, job_id: &str, progress: &ConversionProgress) -> Option<QueuedJob> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.jobs.iter_mut().find(|job| job.id == job_id)?;
+
+        job.percent = progress.percent;
+        let new_state = JobState::from(progress.stage);
+        // Bitiş durumu sadece finish ile yazılır
+        if job.state == new_state || !new_state.is_running() {
+            return None;
+        }
+
+        job.state = new_state;
+        Some(job.clone())
+    }
+
+    /// Records the result of a job started with `start_next`
+    pub fn finish(
+        &self,
+        job_id: &str,
+        result: Result<String, ConversionError>,
+    ) -> Option<QueuedJob> {
+        let mut state = self.state.lock().unwrap();
+        state.running = state.running.saturating_sub(1);
+
+        // İş çalışırken kuyruktan kaldırılmış olabilir
+        let job = state.jobs.iter_mut().find(|job| job.id == job_id)?;
+        match result {
+            Ok(output) => {
+                job.state = JobState::Done;
+                job.percent = 100.0;
+                job.output = Some(output);
+            }
+            Err(ConversionError::Cancelled) => job.state = JobState::Cancelled,
+            Err(e) => {
+                job.state = JobState::Failed;
+                job.error = Some(e);
+            }
+        }
+
+        let finished = job.clone();
+        self.save(&state);
+        Some(finished)
+    }
+
+    /// Moves a job to another position, running jobs can be moved too but it has no effect on them
+    pub fn move_job(&self, job_id: &str, index: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let from = match state.jobs.iter().position(|job| job.id == job_id) {
+            Some(from) => from,
+            None => return false,
+        };
+
+        let job = state.jobs.remove(from);
+        let index = index.min(state.jobs.len());
+        state.jobs.insert(index, job);
+        self.save(&state);
+        true
+    }
+
+    /// Takes a job out of the queue. A running job has to be cancelled separately,
+    /// it keeps its concurrency slot until it stops.
+    pub fn remove(&self, job_id: &str) -> Option<QueuedJob> {
+        let mut state = self.state.lock().unwrap();
+        let index = state.jobs.iter().position(|job| job.id == job_id)?;
+        let job = state.jobs.remove(index);
+        self.save(&state);
+        Some(job)
+    }
+}
@@ -0,0 +1,157 @@
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::codec::{AudioCodec, VideoCodec};
+use crate::output::OutputPolicy;
+use crate::store::{read_json, write_json};
+
+/// Version of the settings file, bump it and add a step to `migrate` when fields change
+pub const SETTINGS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warning => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        // Geliştirme sırasında her şey loglanır
+        if cfg!(debug_assertions) {
+            LogLevel::Debug
+        } else {
+            LogLevel::Info
+        }
+    }
+}
+
+/// User preferences, defaults for every new conversion.
+/// Missing fields get their default so older files keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub version: u32,
+    /// MB
+    pub target_size: f32,
+    pub codec: VideoCodec,
+    /// `None` picks the audio codec for the budget
+    pub audio_codec: Option<AudioCodec>,
+    pub strip_audio: bool,
+    pub output: OutputPolicy,
+    pub log_level: LogLevel,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            target_size: 8.0,
+            codec: VideoCodec::default(),
+            audio_codec: None,
+            strip_audio: false,
+            output: OutputPolicy::default(),
+            log_level: LogLevel::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Fixes values the frontend or a hand edited file may send
+    fn validated(mut self) -> Self {
+        if !self.target_size.is_finite() || self.target_size <= 0.0 {
+            self.target_size = Settings::default().target_size;
+        }
+        self.version = SETTINGS_VERSION;
+        self
+    }
+}
+
+/// Upgrades a settings file written by an older version one step at a time
+fn migrate(mut value: Value) -> Value {
+    let mut version = value["version"].as_u64().unwrap_or(0);
+
+    while version < SETTINGS_VERSION as u64 {
+        match version {
+            // Sürüm alanı eklenmeden önce yazılan dosyalar, alanlar aynı
+            0 => {}
+            _ => break,
+        }
+        version += 1;
+    }
+
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), Value::from(version));
+    }
+    value
+}
+
+/// Settings kept in `settings.json` in the platform config dir
+pub struct SettingsStore {
+    settings: Mutex<Settings>,
+    path: PathBuf,
+}
+
+impl SettingsStore {
+    pub fn load() -> Self {
+        let path = settings_path();
+        let settings = read_json::<Value>(&path)
+            .map(migrate)
+            .and_then(|value| match serde_json::from_value::<Settings>(value) {
+                Ok(settings) => Some(settings.validated()),
+                Err(e) => {
+                    eprintln!("Ayarlar okunamadı, varsayılanlar kullanılıyor: {}", e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        SettingsStore {
+            settings: Mutex::new(settings),
+            path,
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// Replaces and saves the settings, returns what was stored
+    pub fn update(&self, settings: Settings) -> Settings {
+        let settings = settings.validated();
+        *self.settings.lock().unwrap() = settings.clone();
+
+        if let Err(e) = write_json(&self.path, &settings) {
+            eprintln!("Ayarlar kaydedilemedi: {} ({})", self.path.display(), e);
+        }
+        settings
+    }
+}
+
+/// Platform config dir of the app, settings and user presets live here
+pub fn config_dir() -> PathBuf {
+    ProjectDirs::from("com", "rw", "Max8VideoEditor")
+        .map(|dirs| dirs.config_dir().to_path_buf())
+        .unwrap_or_else(|| env::temp_dir().join("Max8VideoEditor"))
+}
+
+fn settings_path() -> PathBuf {
+    config_dir().join("settings.json")
+}
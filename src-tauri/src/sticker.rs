@@ -0,0 +1,209 @@
+use crate::codec::video_args;
+use crate::ffmpeg::{
+    check_output, geometry_filters, get_corrected_video_rate, get_target_bytes, get_total_rate,
+    input_args, null_output, progress_args, run_pass, shrink_until_fits, ConversionError,
+    ConversionProgress, PixelCrop, ProgressReporter, MAX_SIZE_ATTEMPTS,
+};
+use crate::jobs::JobHandle;
+use crate::probe::probe_media;
+
+/// Telegram video sticker rules, one side exactly this long and the other one not longer
+pub const STICKER_SIDE: u32 = 512;
+/// seconds
+pub const STICKER_MAX_DURATION: f32 = 3.0;
+pub const STICKER_MAX_BYTES: u64 = 256 * 1024;
+pub const STICKER_MAX_FPS: f32 = 30.0;
+
+/// The trim stays this much under the limit, the last frame can end a little after the cut
+const DURATION_MARGIN: f32 = 0.05;
+
+/// The first attempt aims this much below the size limit
+const STICKER_SAFETY: f32 = 0.90;
+
+/// End of the trim, cut so the sticker is never longer than `STICKER_MAX_DURATION`
+pub fn get_sticker_end(start_time: Option<f32>, end_time: Option<f32>, clip_duration: f32) -> Option<f32> {
+    let max_duration = STICKER_MAX_DURATION - DURATION_MARGIN;
+    if clip_duration > max_duration {
+        Some(start_time.unwrap_or(0.0).max(0.0) + max_duration)
+    } else {
+        end_time
+    }
+}
+
+/// Size limit in bytes, a smaller target than the platform limit is kept
+pub fn get_sticker_limit(target_size: f32) -> u64 {
+    if target_size > 0.0 {
+        STICKER_MAX_BYTES.min(get_target_bytes(target_size))
+    } else {
+        STICKER_MAX_BYTES
+    }
+}
+
+/// Frame rate cap, the source is never sped up
+pub fn get_sticker_fps(source_fps: Option<f32>) -> f32 {
+    source_fps
+        .filter(|fps| *fps > 0.0)
+        .unwrap_or(STICKER_MAX_FPS)
+        .min(STICKER_MAX_FPS)
+}
+
+/// Video bit rate of the first attempt in kib/s, there is no audio to share the budget with
+pub fn get_sticker_rate(limit: u64, duration: f32) -> f32 {
+    get_total_rate(limit as f32 / 1_000_000.0, duration) * STICKER_SAFETY
+}
+
+/// Two-pass VP9 encode of a video sticker, scaled into a transparent
+/// `STICKER_SIDE` square at `fps` without audio
+pub struct StickerEncode {
+    pub input: String,
+    pub output: String,
+    /// see `VideoCodec::find_encoder`
+    pub encoder: String,
+    pub start_time: Option<f32>,
+    /// see `get_sticker_end`
+    pub end_time: Option<f32>,
+    pub crop: Option<PixelCrop>,
+    /// see `geometry_filters`
+    pub sar_scale: Option<(u32, u32)>,
+    pub fps: f32,
+    /// kib/s
+    pub video_bitrate: f32,
+    /// see `codec::video_args`
+    pub passlog_prefix: String,
+}
+
+impl StickerEncode {
+    /// Runs both passes, returns the size of the output in bytes
+    pub fn run(
+        &self,
+        progress: &mut ProgressReporter,
+        job: &JobHandle,
+    ) -> Result<u64, ConversionError> {
+        for pass in 1..=2 {
+            let args = self.pass_args(pass);
+            eprintln!("Sticker {}. geçiş FFmpeg komutu: {}", pass, args.join(" "));
+            run_pass(pass, args, progress, job)?;
+        }
+
+        progress.report(ConversionProgress::verifying());
+        check_output(&self.output)
+    }
+
+    /// Crop first, then the frame rate cap, the fit into the square and the transparent padding
+    fn filters(&self) -> String {
+        let mut filters = geometry_filters(self.sar_scale, self.crop);
+        filters.push(format!("fps={}", self.fps));
+        // Uzun kenar 512 olur, kısa kenar şeffaf bantla 512'ye tamamlanır
+        filters.push(format!(
+            "scale={side}:{side}:force_original_aspect_ratio=decrease:flags=lanczos,\
+             format=yuva420p,pad={side}:{side}:(ow-iw)/2:(oh-ih)/2:color=black@0,setsar=1",
+            side = STICKER_SIDE
+        ));
+        filters.join(",")
+    }
+
+    fn pass_args(&self, pass: u8) -> Vec<String> {
+        let mut args = progress_args();
+        args.extend(input_args(&self.input, self.start_time, self.end_time));
+        args.extend(vec!["-map".to_string(), "0:v:0".to_string()]);
+        args.extend(video_args(&self.encoder, pass, &self.passlog_prefix));
+        args.extend(vec![
+            "-filter:v".to_string(),
+            self.filters(),
+            "-b:v".to_string(),
+            format!("{}k", self.video_bitrate),
+            "-pix_fmt".to_string(),
+            "yuva420p".to_string(),
+            // Sticker'larda ses olamaz, iki geçişte de ses yok
+            "-an".to_string(),
+            "-sn".to_string(),
+            "-dn".to_string(),
+            "-f".to_string(),
+            "webm".to_string(),
+        ]);
+
+        if pass == 1 {
+            args.push(null_output().to_string());
+        } else {
+            args.push(self.output.clone());
+        }
+        args
+    }
+}
+
+/// Encodes the sticker, correcting the bit rate after every attempt that came out
+/// bigger than `limit` bytes, up to `MAX_SIZE_ATTEMPTS` times. Returns the final size in bytes.
+pub fn encode_sticker_to_size(
+    encode: &mut StickerEncode,
+    limit: u64,
+    duration: f32,
+    progress: &mut ProgressReporter,
+    job: &JobHandle,
+) -> Result<u64, ConversionError> {
+    shrink_until_fits(
+        encode,
+        limit,
+        MAX_SIZE_ATTEMPTS,
+        None,
+        progress,
+        |encode, progress| {
+            eprintln!("Sticker video bit hızı: {}k", encode.video_bitrate);
+            encode.run(progress, job)
+        },
+        |encode, size| {
+            let corrected =
+                get_corrected_video_rate(encode.video_bitrate, 0.0, duration, size, limit);
+            encode.video_bitrate = corrected;
+            corrected > 0.0
+        },
+    )
+}
+
+/// Probes the finished sticker and checks it against every rule, all broken
+/// rules are reported together. Returns the size in bytes.
+pub fn check_sticker(path: &str, limit: u64) -> Result<u64, ConversionError> {
+    let size = check_output(path)?;
+    let media = probe_media(path)?;
+    let mut problems = Vec::new();
+
+    if size > limit {
+        problems.push(format!("{} bytes, at most {} allowed", size, limit));
+    }
+    if !media.format_name.split(',').any(|name| name == "webm") {
+        problems.push(format!("{} container instead of WebM", media.format_name));
+    }
+    if media.duration > STICKER_MAX_DURATION {
+        problems.push(format!(
+            "{:.2} seconds long, at most {} allowed",
+            media.duration, STICKER_MAX_DURATION
+        ));
+    }
+    if !media.audio.is_empty() {
+        problems.push("has an audio track".to_string());
+    }
+
+    match (media.video.len(), media.primary_video()) {
+        (1, Some(video)) => {
+            if video.codec != "vp9" {
+                problems.push(format!("{} video instead of VP9", video.codec));
+            }
+            let (width, height) = (video.width, video.height);
+            if width.max(height) != STICKER_SIDE || width.min(height) > STICKER_SIDE {
+                problems.push(format!(
+                    "{}x{}, one side must be {} px and the other at most that",
+                    width, height, STICKER_SIDE
+                ));
+            }
+            if let Some(fps) = video.fps.filter(|fps| *fps > STICKER_MAX_FPS + 0.01) {
+                problems.push(format!("{:.2} fps, at most {} allowed", fps, STICKER_MAX_FPS));
+            }
+        }
+        (count, _) => problems.push(format!("{} video streams instead of one", count)),
+    }
+
+    if problems.is_empty() {
+        Ok(size)
+    } else {
+        Err(ConversionError::InvalidSticker { problems })
+    }
+}
@@ -0,0 +1,39 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Reads a json file written by `write_json`. A missing or broken file gives `None`,
+/// the app then starts with an empty state instead of failing.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            eprintln!("{} okunamadı: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&text) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("{} bozuk, yok sayılıyor: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Writes to a temp file first and renames it, a crash while writing can't leave half a file
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(parent_dir) = path.parent() {
+        fs::create_dir_all(parent_dir)?;
+    }
+
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path)
+}
@@ -0,0 +1,135 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::ffmpeg::{io_error, ConversionError};
+
+/// Directory inside the system temp dir that holds the workspace of every job
+const WORKSPACE_ROOT: &str = "Max8VideoEditor";
+
+/// Workspaces untouched for longer than this were left behind by a crash
+const STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Temp directory of a single conversion, holds the pass logs and the
+/// intermediate output. It is removed when dropped, so success, failure
+/// and cancel all clean up after themselves.
+pub struct Workspace {
+    dir: PathBuf,
+}
+
+impl Workspace {
+    pub fn create(job_id: &str) -> Result<Self, ConversionError> {
+        let dir = workspace_root().join(sanitize(job_id));
+        fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+
+        Ok(Workspace { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of a file inside the workspace
+    pub fn path(&self, name: &str) -> String {
+        self.dir.join(name).to_string_lossy().to_string()
+    }
+
+    /// Prefix for ffmpeg's `-passlogfile`, unique per job so concurrent jobs
+    /// don't overwrite each other's `-0.log` and `.mbtree` files
+    pub fn passlog_prefix(&self) -> String {
+        self.path("passlog")
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            eprintln!("Geçici klasör silinemedi: {} ({})", self.dir.display(), e);
+        }
+    }
+}
+
+fn workspace_root() -> PathBuf {
+    env::temp_dir().join(WORKSPACE_ROOT).join("jobs")
+}
+
+/// Job ids come from the frontend, keep only characters that are safe in a directory name
+fn sanitize(job_id: &str) -> String {
+    let name: String = job_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if name.is_empty() {
+        "job".to_string()
+    } else {
+        name
+    }
+}
+
+/// Moves a finished file out of the workspace, falls back to copying when
+/// the temp dir is on another drive
+pub fn move_file(from: &str, to: &str) -> Result<(), ConversionError> {
+    let to_path = Path::new(to);
+    if let Some(parent_dir) = to_path.parent() {
+        fs::create_dir_all(parent_dir).map_err(|e| io_error(parent_dir, e))?;
+    }
+
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to)
+        .and_then(|_| fs::remove_file(from))
+        .map_err(|e| io_error(to_path, e))
+}
+
+/// Deletes workspaces left behind by crashed runs, returns how many were removed
+pub fn sweep_stale_workspaces() -> usize {
+    let entries = match fs::read_dir(workspace_root()) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() || !is_stale(&path) {
+            continue;
+        }
+
+        match fs::remove_dir_all(&path) {
+            Ok(_) => removed += 1,
+            Err(e) => eprintln!("Eski geçici klasör silinemedi: {} ({})", path.display(), e),
+        }
+    }
+
+    removed
+}
+
+/// A workspace is stale when neither it nor any file in it changed recently,
+/// a running job keeps writing its pass log
+fn is_stale(dir: &Path) -> bool {
+    let mut newest = modified(dir);
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            newest = newest.max(modified(&entry.path()));
+        }
+    }
+
+    match newest.and_then(|time| SystemTime::now().duration_since(time).ok()) {
+        Some(age) => age > STALE_AFTER,
+        None => true,
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
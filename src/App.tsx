@@ -7,6 +7,7 @@ import Convert from "./pages/Convert";
 import { message } from "@tauri-apps/api/dialog";
 import Success from "./pages/Success";
 import VideoEditor from "./pages/VideoEditor";
+import Queue from "./pages/Queue";
 
 function App() {
   const [video, setVideo] = useState<string>();
@@ -24,6 +25,7 @@ function App() {
         <Route path="/editor/:filePath" element={<VideoEditor />} />
         <Route path="/convert/:filePath" element={<Convert />} />
         <Route path="/success/:outputFolder" element={<Success />} />
+        <Route path="/queue" element={<Queue />} />
       </Routes>
     </div>
   );
@@ -3,20 +3,55 @@ import { useEffect, useMemo, useState } from "react";
 import { message } from "@tauri-apps/api/dialog";
 import { invoke } from "@tauri-apps/api";
 import debounce from "lodash.debounce";
-import { fromBase64, toBase64 } from "../utils";
+import { fromBase64, newJobId, requestDefaults, toBase64 } from "../utils";
 import { listen } from "@tauri-apps/api/event";
 
+// Backend'in conversion_progress event'i ile gönderdiği ilerleme bilgisi
+interface ConversionProgress {
+  stage: "probing" | "pass1" | "pass2" | "verifying" | "copying" | "encoding" | "done";
+  pass: number;
+  attempt: number;
+  percent: number;
+  pass_percent: number;
+  eta: number | null;
+  bitrate: number | null;
+  speed: number | null;
+  fps: number | null;
+  total_size: number | null;
+}
+
+// "x/2" sadece iki geçişli encode'da, kopyalama ve tek geçişli encode'larda aşamanın adı
+const stageLabels: { [key: string]: string } = {
+  pass1: "Pass 1/2",
+  pass2: "Pass 2/2",
+  verifying: "Checking size",
+  copying: "Copying",
+  encoding: "Encoding",
+  done: "Done",
+};
+
+function formatEta(seconds: number) {
+  const total = Math.max(0, Math.round(seconds));
+  const minutes = Math.floor(total / 60);
+  const secs = total % 60;
+  return `${minutes}:${secs.toString().padStart(2, "0")}`;
+}
+
 export default function Convert() {
   const router = useNavigate();
   const [convertStatus, setConvertStatus] = useState<string>("Compressing");
   const [progress, setProgress] = useState<number>(0);
+  const [details, setDetails] = useState<ConversionProgress | null>(null);
   const [fail, setFail] = useState<boolean>(false);
+  const [paused, setPaused] = useState<boolean>(false);
+  const jobId = useMemo(() => newJobId(), []);
   const { filePath } = useParams();
 
   useEffect(() => {
     // İlerleme durumunu dinleyen fonksiyon
-    const unlistenProgress = listen("conversion_progress", (event) => {
-      setProgress(event.payload as number);
+    const unlistenProgress = listen<ConversionProgress>("conversion_progress", (event) => {
+      setProgress(event.payload.percent);
+      setDetails(event.payload);
     });
 
     // Component unmount edildiğinde listener'ı kaldırıyoruz
@@ -36,27 +71,46 @@ export default function Convert() {
 
       setProgress(0);
 
-      const out: any = await invoke("convert_video", {
+      // Boyut, codec ve çıktı ayarları kaydedilmiş varsayılanlardan gelir
+      const defaults = await requestDefaults();
+      const out: string = await invoke("convert_video", {
+        ...defaults,
         input: decodedFilePath,
-        targetSize: 8,
+        jobId,
       });
 
-      if (!out) {
-        setConvertStatus("Failed to convert. Video can not be compressed to 8mb");
-        setFail(true);
-        return;
-      }
-
       setConvertStatus("Successfully converted");
       setProgress(100);
 
       return router(`/success/${toBase64(out)}`);
     } catch (err: any) {
-      setConvertStatus("An unexpected error has occured. Check the console for details.");
+      // Backend ConversionError: { kind, message, stderr }
+      if (err?.kind === "cancelled") {
+        setConvertStatus("Conversion cancelled");
+      } else if (err?.message) {
+        setConvertStatus(`Failed to convert. ${err.message}`);
+        if (err.stderr) {
+          console.log(err.stderr);
+        }
+      } else {
+        setConvertStatus("An unexpected error has occured. Check the console for details.");
+      }
+      setFail(true);
       console.log(err);
     }
   };
 
+  const cancelConversion = async () => {
+    await invoke("cancel_conversion", { jobId });
+  };
+
+  const togglePause = async () => {
+    const ok: boolean = await invoke(paused ? "resume_conversion" : "pause_conversion", { jobId });
+    if (ok) {
+      setPaused(!paused);
+    }
+  };
+
   const debouncedEventHandler = useMemo(() => debounce(convertVideo, 300), []);
   useEffect(() => {
     debouncedEventHandler();
@@ -71,7 +125,18 @@ export default function Convert() {
         <div className="w-64 bg-gray-700 rounded-full h-4 mb-4">
           <div className="bg-blue-500 h-4 rounded-full transition-all duration-300 ease-in-out" style={{ width: `${progress}%` }}></div>
         </div>
-        <div className="text-white mb-4">{progress.toFixed(0)}%</div>
+        <div className="text-white mb-1">{progress.toFixed(0)}%</div>
+        {details && stageLabels[details.stage] ? (
+          <div className="text-gray-400 text-sm mb-4">
+            {details.attempt > 1 ? `Attempt ${details.attempt} · ` : ""}
+            {stageLabels[details.stage]}
+            {details.speed ? ` · ${details.speed.toFixed(2)}x` : ""}
+            {details.bitrate ? ` · ${details.bitrate.toFixed(0)} kbit/s` : ""}
+            {details.eta != null ? ` · ETA ${formatEta(details.eta)}` : ""}
+          </div>
+        ) : (
+          <div className="mb-4"></div>
+        )}
 
         <svg className="text-white" height={42} width={42} version="1.1" id="L9" xmlns="http://www.w3.org/2000/svg" xmlnsXlink="http://www.w3.org/1999/xlink" x="0px" y="0px" viewBox="0 0 100 100" enableBackground="new 0 0 0 0" xmlSpace="preserve">
           <path fill="#fff" d="M73,50c0-12.7-10.3-23-23-23S27,37.3,27,50 M30.9,50c0-10.5,8.5-19.1,19.1-19.1S69.1,39.5,69.1,50">
@@ -86,7 +151,14 @@ export default function Convert() {
           </button>
         </div>
       ) : (
-        <></>
+        <div className="flex flex-row space-x-2 mt-2">
+          <button onClick={togglePause} className="bg-gray-800 text-gray-300 px-2 py-1.5">
+            {paused ? "Resume" : "Pause"}
+          </button>
+          <button onClick={cancelConversion} className="bg-gray-800 text-gray-300 px-2 py-1.5">
+            Cancel
+          </button>
+        </div>
       )}
     </div>
   );
@@ -5,7 +5,8 @@ import { useNavigate } from "react-router-dom";
 import { Buffer } from "buffer";
 import debounce from "lodash.debounce";
 import { appDir } from "@tauri-apps/api/path";
-import { toBase64 } from "../utils";
+import { invoke } from "@tauri-apps/api/tauri";
+import { requestDefaults, toBase64 } from "../utils";
 
 export default function Menu() {
   const [isDrag, setIsDrag] = useState<boolean>(false);
@@ -18,6 +19,20 @@ export default function Menu() {
 
   const debouncedEventHandler = useMemo(() => debounce(handleNavigate, 300), []);
 
+  const handleEnqueue = async (filePaths: string[]) => {
+    try {
+      const defaults = await requestDefaults();
+      await invoke("enqueue_jobs", {
+        requests: filePaths.map((input) => ({ ...defaults, input })),
+      });
+      navigate("/queue");
+    } catch (err: any) {
+      await message(err?.message || String(err));
+    }
+  };
+
+  const debouncedEnqueue = useMemo(() => debounce(handleEnqueue, 300), []);
+
   useEffect(() => {
     let unlisten: UnlistenFn;
     let unlistenFileDrop: UnlistenFn;
@@ -45,6 +60,12 @@ export default function Menu() {
           return;
         }
 
+        // Birden fazla video bırakıldıysa hepsi kuyruğa eklenir
+        if (event.payload.length > 1) {
+          debouncedEnqueue(event.payload);
+          return;
+        }
+
         debouncedEventHandler(event.payload[0]);
       });
     };
@@ -70,7 +91,7 @@ export default function Menu() {
 
   const fileClick = async (e: any) => {
     const selected = await open({
-      multiple: false,
+      multiple: true,
       filters: [
         {
           name: "Video",
@@ -79,10 +100,14 @@ export default function Menu() {
       ],
     });
 
-    if (Array.isArray(selected)) {
-      await message("Please only select one file");
+    if (selected === null) {
       return;
-    } else if (selected === null) {
+    } else if (Array.isArray(selected)) {
+      if (selected.length > 1) {
+        debouncedEnqueue(selected);
+      } else if (selected.length === 1) {
+        debouncedEventHandler(selected[0]);
+      }
       return;
     }
 
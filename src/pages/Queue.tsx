@@ -0,0 +1,248 @@
+import { useEffect, useState } from "react";
+import { useNavigate } from "react-router-dom";
+import { invoke } from "@tauri-apps/api/tauri";
+import { listen } from "@tauri-apps/api/event";
+import { open } from "@tauri-apps/api/dialog";
+import { requestDefaults } from "../utils";
+
+// Backend'deki QueuedJob ile aynı yapı
+interface QueuedJob {
+  id: string;
+  request: { input: string; targetSize: number; clips: { input: string }[] };
+  state: "queued" | "probing" | "pass1" | "pass2" | "verifying" | "copying" | "encoding" | "done" | "failed" | "cancelled";
+  percent: number;
+  output: string | null;
+  error: { kind: string; message: string; stderr: string | null } | null;
+}
+
+interface QueueSnapshot {
+  jobs: QueuedJob[];
+  concurrency: number;
+}
+
+// Backend'deki HistoryEntry
+interface HistoryEntry {
+  id: string;
+  input: string;
+  output: string | null;
+  targetSize: number;
+  codec: string;
+  startTime: number | null;
+  endTime: number | null;
+  details: {
+    clipDuration: number | null;
+    width: number | null;
+    height: number | null;
+    fps: number | null;
+    audio: { codec: string; bitrate: number } | null;
+  };
+  state: "done" | "failed" | "cancelled";
+  size: number | null;
+  error: string | null;
+  startedAt: string;
+  finishedAt: string;
+  elapsed: number;
+}
+
+const stateLabels: { [key: string]: string } = {
+  queued: "Queued",
+  probing: "Reading video",
+  pass1: "Pass 1/2",
+  pass2: "Pass 2/2",
+  verifying: "Checking size",
+  copying: "Copying",
+  encoding: "Encoding",
+  done: "Done",
+  failed: "Failed",
+  cancelled: "Cancelled",
+};
+
+const runningStates = ["probing", "pass1", "pass2", "verifying", "copying", "encoding"];
+
+function fileName(path: string) {
+  return path.split(/[\\/]/).pop() || path;
+}
+
+export default function Queue() {
+  const router = useNavigate();
+  const [jobs, setJobs] = useState<QueuedJob[]>([]);
+  const [concurrency, setConcurrency] = useState<number>(1);
+  const [history, setHistory] = useState<HistoryEntry[]>([]);
+
+  const loadHistory = () => {
+    invoke<HistoryEntry[]>("get_history").then(setHistory);
+  };
+
+  useEffect(() => {
+    const applySnapshot = (snapshot: QueueSnapshot) => {
+      setJobs(snapshot.jobs);
+      setConcurrency(snapshot.concurrency);
+    };
+
+    invoke<QueueSnapshot>("get_queue").then(applySnapshot);
+    loadHistory();
+
+    const unlistenQueue = listen<QueueSnapshot>("queue_changed", (event) => applySnapshot(event.payload));
+
+    // Tek bir işin durumu değişti
+    const unlistenState = listen<QueuedJob>("job_state", (event) => {
+      setJobs((current) => current.map((job) => (job.id === event.payload.id ? event.payload : job)));
+      // Biten iş geçmişe yazılmıştır
+      if (["done", "failed", "cancelled"].includes(event.payload.state)) {
+        loadHistory();
+      }
+    });
+
+    const unlistenProgress = listen<{ jobId: string; progress: { percent: number } }>("job_progress", (event) => {
+      const { jobId, progress } = event.payload;
+      setJobs((current) => current.map((job) => (job.id === jobId ? { ...job, percent: progress.percent } : job)));
+    });
+
+    return () => {
+      unlistenQueue.then((unlisten) => unlisten());
+      unlistenState.then((unlisten) => unlisten());
+      unlistenProgress.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const addVideos = async () => {
+    const selected = await open({
+      multiple: true,
+      filters: [{ name: "Video", extensions: ["mp4", "mkv", "mov", "m4a"] }],
+    });
+    if (selected === null) {
+      return;
+    }
+
+    const inputs = Array.isArray(selected) ? selected : [selected];
+    const defaults = await requestDefaults();
+    await invoke("enqueue_jobs", {
+      requests: inputs.map((input) => ({ ...defaults, input })),
+    });
+  };
+
+  // Seçilen videolar seçim sırasıyla tek bir çıktıda birleştirilir
+  const mergeVideos = async () => {
+    const selected = await open({
+      multiple: true,
+      filters: [{ name: "Video", extensions: ["mp4", "mkv", "mov", "m4a"] }],
+    });
+    if (!Array.isArray(selected) || selected.length < 2) {
+      return;
+    }
+
+    const defaults = await requestDefaults();
+    await invoke("enqueue_jobs", {
+      requests: [{ ...defaults, input: selected[0], clips: selected.map((input) => ({ input })) }],
+    });
+  };
+
+  const moveJob = async (jobId: string, index: number) => {
+    await invoke("move_job", { jobId, index: Math.max(0, index) });
+  };
+
+  const removeJob = async (jobId: string) => {
+    await invoke("remove_job", { jobId });
+  };
+
+  const clearHistory = async () => {
+    await invoke("clear_history");
+    setHistory([]);
+  };
+
+  const changeConcurrency = async (value: number) => {
+    const applied: number = await invoke("set_concurrency", { concurrency: value });
+    setConcurrency(applied);
+  };
+
+  return (
+    <div className="h-screen flex flex-col text-white p-4">
+      <div className="flex items-center justify-between mb-4">
+        <h1 className="text-2xl font-bold">Queue</h1>
+        <div className="flex items-center space-x-2 text-sm">
+          <label className="text-gray-300">At the same time</label>
+          <select value={concurrency} onChange={(e) => changeConcurrency(Number(e.target.value))} className="bg-gray-700 rounded-md px-2 py-1">
+            {[1, 2, 3, 4].map((n) => (
+              <option key={n} value={n}>
+                {n}
+              </option>
+            ))}
+          </select>
+          <button onClick={addVideos} className="bg-blue-600 hover:bg-blue-700 px-3 py-1.5 rounded-md">
+            Add Videos
+          </button>
+          <button onClick={mergeVideos} className="bg-gray-700 hover:bg-gray-600 px-3 py-1.5 rounded-md">
+            Merge Videos
+          </button>
+          <button onClick={() => router("/")} className="bg-gray-800 text-gray-300 px-3 py-1.5 rounded-md">
+            Menu
+          </button>
+        </div>
+      </div>
+
+      <div className="flex-1 overflow-y-auto space-y-2">
+        {jobs.length === 0 && <div className="text-gray-400 text-center mt-10">The queue is empty</div>}
+
+        {jobs.map((job, index) => (
+          <div key={job.id} className="bg-gray-900 rounded-md p-3">
+            <div className="flex items-center justify-between">
+              <div className="truncate mr-2">
+                <div className="truncate">
+                  {fileName(job.request.input)}
+                  {job.request.clips.length > 1 ? ` + ${job.request.clips.length - 1} more` : ""}
+                </div>
+                <div className="text-xs text-gray-400">
+                  {job.request.targetSize} MB · {stateLabels[job.state]}
+                  {job.error ? ` · ${job.error.message}` : ""}
+                  {job.output ? ` · ${fileName(job.output)}` : ""}
+                </div>
+              </div>
+              <div className="flex space-x-1 text-sm shrink-0">
+                <button onClick={() => moveJob(job.id, index - 1)} disabled={index === 0} className="bg-gray-800 px-2 py-1 rounded disabled:opacity-40">
+                  ↑
+                </button>
+                <button onClick={() => moveJob(job.id, index + 1)} disabled={index === jobs.length - 1} className="bg-gray-800 px-2 py-1 rounded disabled:opacity-40">
+                  ↓
+                </button>
+                <button onClick={() => removeJob(job.id)} className="bg-gray-800 px-2 py-1 rounded">
+                  {runningStates.includes(job.state) ? "Cancel" : "Remove"}
+                </button>
+              </div>
+            </div>
+            {runningStates.includes(job.state) && (
+              <div className="w-full bg-gray-700 rounded-full h-2 mt-2">
+                <div className="bg-blue-500 h-2 rounded-full transition-all duration-300 ease-in-out" style={{ width: `${job.percent}%` }}></div>
+              </div>
+            )}
+          </div>
+        ))}
+
+        {history.length > 0 && (
+          <div className="pt-4">
+            <div className="flex items-center justify-between mb-2">
+              <h2 className="text-lg font-bold">History</h2>
+              <button onClick={clearHistory} className="bg-gray-800 text-gray-300 text-sm px-2 py-1 rounded">
+                Clear
+              </button>
+            </div>
+            {history.map((entry) => (
+              <div key={entry.id} className="text-sm border-b border-gray-800 py-1.5">
+                <div className="truncate">{fileName(entry.output || entry.input)}</div>
+                <div className="text-xs text-gray-400">
+                  {new Date(entry.finishedAt).toLocaleString()} · {stateLabels[entry.state]}
+                  {entry.size != null ? ` · ${(entry.size / 1000000).toFixed(2)} MB` : ""}
+                  {entry.details?.clipDuration != null ? ` · ${entry.details.clipDuration.toFixed(1)}s clip` : ""}
+                  {entry.details?.width != null ? ` · ${entry.details.width}${entry.details.height != null ? `x${entry.details.height}` : "px"}` : ""}
+                  {entry.details?.fps != null ? ` ${entry.details.fps} fps` : ""}
+                  {entry.details?.audio ? ` · ${entry.details.audio.bitrate}k ${entry.details.audio.codec}` : ""}
+                  {entry.elapsed ? ` · took ${Math.round(entry.elapsed)}s` : ""}
+                  {entry.error ? ` · ${entry.error}` : ""}
+                </div>
+              </div>
+            ))}
+          </div>
+        )}
+      </div>
+    </div>
+  );
+}
@@ -1,10 +1,11 @@
 import { useEffect, useRef, useState } from "react";
 import { useNavigate, useParams } from "react-router-dom";
-import { fromBase64, toBase64 } from "../utils";
+import { fromBase64, newJobId, Preset, Settings, toBase64 } from "../utils";
 import { invoke } from "@tauri-apps/api/tauri";
 import { listen } from "@tauri-apps/api/event";
 import { convertFileSrc } from "@tauri-apps/api/tauri";
 import { appWindow } from "@tauri-apps/api/window";
+import { message, open, save } from "@tauri-apps/api/dialog";
 
 // Video URL'sini loglayan yardımcı fonksiyon
 const logVideoUrl = async (url: string, context: string) => {
@@ -40,6 +41,42 @@ const logError = async (error: any, context: string) => {
   }
 };
 
+// Backend'deki Estimate
+interface Estimate {
+  clipDuration: number;
+  videoRate: number;
+  audio: { codec: string; bitrate: number } | null;
+  format: { width: number; height: number; fps: number | null } | null;
+  quality: "low" | "medium" | "high" | "excellent";
+  minSize: number;
+  feasible: boolean;
+  maxDuration: number | null;
+  streamCopy: boolean;
+}
+
+// Backend'deki probe sonucunun ses akışı
+interface AudioStreamInfo {
+  index: number;
+  codec: string;
+  channels: number | null;
+  language: string | null;
+}
+
+const qualityLabels: { [key: string]: string } = {
+  low: "Low",
+  medium: "Medium",
+  high: "High",
+  excellent: "Excellent",
+};
+
+// Codec seçim listesinde gösterilecek isimler
+const codecLabels: { [key: string]: string } = {
+  h264: "H.264 (MP4, plays everywhere)",
+  h265: "H.265 / HEVC (MP4)",
+  vp9: "VP9 (WebM)",
+  av1: "AV1 (MP4)",
+};
+
 // TypeScript tanımlamaları için basit arayüzler
 interface Crop {
   unit: string;
@@ -99,9 +136,35 @@ export default function VideoEditor() {
   const [playing, setPlaying] = useState<boolean>(false);
   const [currentTime, setCurrentTime] = useState<number>(0);
   const [timeRange, setTimeRange] = useState({ start: 0, end: 100 });
+  // Saklanacak parçalar, boş değilse başlangıç/bitiş yerine bunlar birleştirilir
+  const [keptRanges, setKeptRanges] = useState<{ start: number; end: number }[]>([]);
   const [progress, setProgress] = useState<number>(0);
   // Çıkış boyutu için state ekle
   const [targetSize, setTargetSize] = useState<number>(8); // Varsayılan 8MB
+  // Video codec seçimi, liste paketlenen ffmpeg'in desteklediklerinden gelir
+  const [codec, setCodec] = useState<string>("h264");
+  const [availableCodecs, setAvailableCodecs] = useState<string[]>(["h264"]);
+  // Ses codec'i, boş bırakılırsa bütçeye göre otomatik seçilir
+  const [audioCodec, setAudioCodec] = useState<string>("");
+  // Video yerine GIF, hareketli WebP, sticker ya da sadece ses, boş bırakılırsa video
+  const [exportFormat, setExportFormat] = useState<string>("");
+  // Sadece ses çıkarılırken hangi ses akışının alınacağı
+  const [audioStreams, setAudioStreams] = useState<AudioStreamInfo[]>([]);
+  const [audioStream, setAudioStream] = useState<number>(0);
+  // Seçilen ayarlarla çıktının tahmini, hedef tutmuyorsa uyarı gösterilir
+  const [estimate, setEstimate] = useState<Estimate | null>(null);
+  // Platform ayarı seçilirse boyut, codec ve ses onun sınırlarına göre ayarlanır
+  const [presets, setPresets] = useState<Preset[]>([]);
+  const [presetId, setPresetId] = useState<string>("");
+  // Ses kaldırılırsa bütçenin tamamı videoya kalır
+  const [stripAudio, setStripAudio] = useState<boolean>(false);
+  // Çıktı klasörü, dosya adı şablonu ve dosya varsa ne yapılacağı
+  const [outputLocation, setOutputLocation] = useState<string>("fixed");
+  const [fixedDir, setFixedDir] = useState<string | null>(null);
+  const [template, setTemplate] = useState<string>("{stem}-{size}m.{ext}");
+  const [collision, setCollision] = useState<string>("autoIncrement");
+  // Kayıtlı ayarlar, seçimler dönüştürmede yeni varsayılan olarak kaydedilir
+  const [settings, setSettings] = useState<Settings | null>(null);
 
   // Player hazır olduğunda true olacak
   const [playerReady, setPlayerReady] = useState<boolean>(false);
@@ -143,6 +206,9 @@ export default function VideoEditor() {
 
   // İşleme durumu mesajını için state
   const [processingMessage, setProcessingMessage] = useState<string>("Analyzing video...");
+  // Çalışan dönüştürme işinin id'si, iptal ve duraklatma için
+  const [jobId, setJobId] = useState<string | null>(null);
+  const [paused, setPaused] = useState<boolean>(false);
 
   // Timeline tıklama fonksiyonu
   const handleTimelineClick = (e: React.MouseEvent) => {
@@ -699,23 +765,176 @@ export default function VideoEditor() {
     );
   };
 
+  // Kayıtlı varsayılanları yükle
+  useEffect(() => {
+    invoke<Settings>("get_settings")
+      .then((saved) => {
+        setSettings(saved);
+        setTargetSize(saved.targetSize);
+        setCodec(saved.codec);
+        setAudioCodec(saved.audioCodec || "");
+        setStripAudio(saved.stripAudio);
+        setOutputLocation(saved.output.location.kind);
+        setFixedDir(saved.output.location.dir || null);
+        setTemplate(saved.output.template);
+        setCollision(saved.output.collision);
+      })
+      .catch((err) => logError(err, "get_settings"));
+  }, []);
+
+  // Son kullanılan seçimler bir sonraki açılışta varsayılan olur
+  const saveDefaults = async () => {
+    if (!settings) {
+      return;
+    }
+    try {
+      const saved = await invoke<Settings>("update_settings", {
+        settings: { ...settings, targetSize, codec, audioCodec: audioCodec || null, stripAudio, output: outputPolicy() },
+      });
+      setSettings(saved);
+    } catch (err) {
+      logError(err, "update_settings");
+    }
+  };
+
+  const loadPresets = () => {
+    invoke<Preset[]>("get_presets")
+      .then(setPresets)
+      .catch((err) => logError(err, "get_presets"));
+  };
+
+  useEffect(loadPresets, []);
+
+  const selectedPreset = presets.find((p) => p.id === presetId) || null;
+
+  const selectPreset = (id: string) => {
+    setPresetId(id);
+    const preset = presets.find((p) => p.id === id);
+    if (preset) {
+      setTargetSize(preset.maxSize);
+      // Platformun belirlemediği codec'ler kullanıcının seçiminde kalır
+      if (preset.codec) {
+        setCodec(preset.codec);
+      }
+      if (preset.audioCodec) {
+        setAudioCodec(preset.audioCodec);
+      }
+      setStripAudio(preset.stripAudio);
+    }
+  };
+
+  const importPresets = async () => {
+    const selected = await open({ filters: [{ name: "Presets", extensions: ["json"] }] });
+    if (typeof selected !== "string") {
+      return;
+    }
+    try {
+      const imported: Preset[] = await invoke("import_presets", { path: selected });
+      loadPresets();
+      await message(`${imported.length} preset(s) imported`, "Presets");
+    } catch (err: any) {
+      logError(err, "import_presets");
+      await message(err?.message || String(err), "Could not import presets");
+    }
+  };
+
+  const exportPresets = async () => {
+    const path = await save({ defaultPath: "presets.json", filters: [{ name: "Presets", extensions: ["json"] }] });
+    if (!path) {
+      return;
+    }
+    try {
+      // Hazır ayar seçiliyse sadece o, değilse kullanıcının tüm ayarları
+      await invoke("export_presets", { path, ids: presetId ? [presetId] : null });
+    } catch (err: any) {
+      logError(err, "export_presets");
+      await message(err?.message || String(err), "Could not export presets");
+    }
+  };
+
+  // Seçimler değişince tahmini yenile, kaydırıcı bırakılana kadar beklenir
+  useEffect(() => {
+    if (!originalFilePath || duration <= 0) {
+      return;
+    }
+    const timer = setTimeout(() => {
+      invoke<Estimate>("estimate_conversion", {
+        input: originalFilePath,
+        targetSize,
+        startTime: timeRange.start > 0 ? timeRange.start : null,
+        endTime: timeRange.end < duration ? timeRange.end : null,
+        ranges: keptRanges.length > 0 ? keptRanges : null,
+        crop: cropMode && crop.width > 0 && crop.height > 0 && crop.width < 100 && crop.height < 100 ? { x: crop.x, y: crop.y, width: crop.width, height: crop.height } : null,
+        codec,
+        audioCodec: audioCodec || null,
+        stripAudio,
+        preset: presetId || null,
+      })
+        .then(setEstimate)
+        .catch(() => setEstimate(null));
+    }, 400);
+    return () => clearTimeout(timer);
+  }, [originalFilePath, duration, timeRange, keptRanges, targetSize, codec, audioCodec, stripAudio, presetId, cropMode, crop]);
+
+  // Ses akışları dosya değişince okunur
+  useEffect(() => {
+    setAudioStream(0);
+    if (!originalFilePath) {
+      setAudioStreams([]);
+      return;
+    }
+    invoke("probe_media", { input: originalFilePath })
+      .then((info: any) => setAudioStreams(info.audio))
+      .catch(() => setAudioStreams([]));
+  }, [originalFilePath]);
+
+  // Seçili aralık saklanacak parçalara eklenir, sıralama backend'de yapılır
+  const addKeptRange = () => {
+    setKeptRanges((current) => [...current, { start: timeRange.start, end: timeRange.end }].sort((a, b) => a.start - b.start));
+  };
+
+  // Hedefe sığan en uzun parça seçilir, başlangıç yerinde kalır
+  const trimToFit = () => {
+    if (estimate?.maxDuration != null) {
+      setTimeRange((prev) => ({ ...prev, end: Math.min(duration, prev.start + estimate.maxDuration!) }));
+    }
+  };
+
+  // Desteklenen codec'leri yükle
+  useEffect(() => {
+    invoke<string[]>("get_available_codecs")
+      .then((codecs) => {
+        if (codecs.length > 0) {
+          setAvailableCodecs(codecs);
+        }
+      })
+      .catch((err) => logError(err, "get_available_codecs"));
+  }, []);
+
   // useEffect ile event listener ekleyelim
   useEffect(() => {
     // Video işleme durumunu dinlemek için Tauri event listener
     const setupProcessListener = async () => {
       const unlisten = await listen("conversion_progress", (event: any) => {
-        // Gelen progress değerini güncelle
-        const progressValue = (event.payload as number) || 0;
-        setProgress(progressValue);
-
-        // İşlem ilerlemesine göre durumu güncelle
-        if (progressValue <= 25) {
-          setProcessingMessage("Analyzing video...");
-        } else if (progressValue <= 50) {
-          setProcessingMessage("Trimming content...");
-        } else if (progressValue <= 75) {
-          setProcessingMessage("Applying effects...");
-        } else {
+        // Gelen progress değerini güncelle, 0 olursa bar görünmeyeceği için en az 1
+        const progressValue = event.payload?.percent || 0;
+        setProgress(Math.max(progressValue, 1));
+
+        // Hangi geçişte olduğumuza göre durumu güncelle
+        const eta = event.payload?.eta != null ? ` (~${Math.ceil(event.payload.eta)}s left)` : "";
+        // Çıktı hedef boyutu tutmadıysa backend tekrar encode eder
+        const attempt = event.payload?.attempt > 1 ? ` [attempt ${event.payload.attempt}]` : "";
+        if (event.payload?.stage === "copying") {
+          // Kaynak zaten sığıyor, encode edilmeden kopyalanıyor
+          setProcessingMessage(`Copying video...${eta}`);
+        } else if (event.payload?.stage === "encoding" && progressValue < 100) {
+          // GIF, WebP ve ses tek geçişte encode edilir, sığmazsa küçültülüp tekrar denenir
+          setProcessingMessage(`Encoding...${eta}${attempt}`);
+        } else if (event.payload?.pass === 1) {
+          setProcessingMessage(`Analyzing video...${eta}${attempt}`);
+        } else if (event.payload?.pass === 2 && progressValue < 100) {
+          setProcessingMessage(`Encoding video...${eta}${attempt}`);
+        } else if (progressValue >= 100) {
           setProcessingMessage("Optimizing output...");
         }
       });
@@ -729,7 +948,86 @@ export default function VideoEditor() {
     setupProcessListener();
   }, []);
 
+  const outputPolicy = () => ({
+    location: outputLocation === "fixed" ? { kind: "fixed", dir: fixedDir } : { kind: outputLocation },
+    template,
+    collision,
+  });
+
+  // GIF ve WebP animasyon olarak, sticker kendi kurallarıyla dönüştürülür
+  const exportOptions = () => ({
+    animation: exportFormat === "gif" || exportFormat === "webp" ? exportFormat : null,
+    sticker: exportFormat === "sticker",
+    audioOnly: exportFormat.startsWith("audio-") ? exportFormat.slice("audio-".length) : null,
+    audioStream,
+  });
+
+  // "Her seferinde sor" seçiliyse klasör seçtirilir, iptal edilirse undefined döner
+  const askOutputDir = async (): Promise<string | null | undefined> => {
+    if (outputLocation !== "ask") {
+      return null;
+    }
+    const selected = await open({ directory: true });
+    if (selected === null || Array.isArray(selected)) {
+      return undefined;
+    }
+    return selected;
+  };
+
+  const chooseFixedDir = async () => {
+    const selected = await open({ directory: true });
+    if (typeof selected === "string") {
+      setFixedDir(selected);
+    }
+  };
+
+  // Aynı ayarlarla kuyruğa ekle, dönüştürme kuyruk sayfasında sırayla yapılır
+  const handleAddToQueue = async () => {
+    const cropSettings =
+      cropMode && crop.width > 0 && crop.height > 0 && crop.width < 100 && crop.height < 100
+        ? { x: crop.x, y: crop.y, width: crop.width, height: crop.height }
+        : null;
+
+    const outputDir = await askOutputDir();
+    if (outputDir === undefined) {
+      return;
+    }
+    await saveDefaults();
+
+    try {
+      await invoke("enqueue_jobs", {
+        requests: [
+          {
+            input: originalFilePath,
+            targetSize,
+            startTime: timeRange.start > 0 ? timeRange.start : null,
+            endTime: timeRange.end < duration ? timeRange.end : null,
+            ranges: keptRanges,
+            crop: cropSettings,
+            codec,
+            audioCodec: audioCodec || null,
+            stripAudio,
+            outputDir,
+            output: outputPolicy(),
+            preset: selectedPreset,
+            ...exportOptions(),
+          },
+        ],
+      });
+      router("/queue");
+    } catch (err: any) {
+      logError(err, "enqueue_jobs");
+      await message(err?.message || String(err), "Could not add to queue");
+    }
+  };
+
   const handleExport = async () => {
+    const outputDir = await askOutputDir();
+    if (outputDir === undefined) {
+      return;
+    }
+    await saveDefaults();
+
     try {
       // İşlem başlangıcında progress barı göster
       setProgress(1); // 0 olursa görünmeyeceği için 1'den başlatıyoruz
@@ -759,39 +1057,71 @@ export default function VideoEditor() {
         });
       }
 
+      const currentJobId = newJobId();
+      setJobId(currentJobId);
+      setPaused(false);
+
       // Video kesme, crop ve metin ekleme işlemlerini gerçekleştir
-      const out: any = await invoke("convert_video", {
+      const out: string = await invoke("convert_video", {
+        jobId: currentJobId,
         input: originalFilePath,
         targetSize: targetSize,
         startTime: timeRange.start > 0 ? timeRange.start : null,
         endTime: timeRange.end < duration ? timeRange.end : null,
+        ranges: keptRanges.length > 0 ? keptRanges : null,
         crop: cropSettings,
+        codec,
+        audioCodec: audioCodec || null,
+        stripAudio,
+        outputDir,
+        output: outputPolicy(),
+        preset: presetId || null,
+        ...exportOptions(),
       });
 
       // İşlem bittiğinde progress'i temizle
       setProgress(0);
-
-      if (!out) {
-        await invoke("log_to_file_js", {
-          message: "Dönüştürme işlemi başarısız oldu veya boş sonuç döndü",
-          level: "error",
-          category: "VideoExport",
-        });
-        return;
-      }
+      setJobId(null);
 
       router(`/success/${toBase64(out)}`);
-    } catch (err) {
+    } catch (err: any) {
       // Hata durumunda progress'i temizle
       setProgress(0);
+      setJobId(null);
       console.error("Export error:", err);
 
+      // Kullanıcı iptal ettiyse hata gösterme
+      if (err?.kind === "cancelled") {
+        return;
+      }
+
+      // Backend ConversionError döndürür: { kind, message, stderr }
+      const errorMessage = err?.message || String(err);
+
       // Hatayı logla
       await invoke("log_to_file_js", {
-        message: `Dönüştürme hatası: ${String(err)}`,
+        message: `Dönüştürme hatası: ${errorMessage}`,
         level: "error",
         category: "VideoExport",
       });
+
+      await message(errorMessage, "Conversion failed");
+    }
+  };
+
+  const handleCancel = async () => {
+    if (jobId) {
+      await invoke("cancel_conversion", { jobId });
+    }
+  };
+
+  const handlePauseToggle = async () => {
+    if (!jobId) {
+      return;
+    }
+    const ok: boolean = await invoke(paused ? "resume_conversion" : "pause_conversion", { jobId });
+    if (ok) {
+      setPaused(!paused);
     }
   };
 
@@ -945,6 +1275,11 @@ export default function VideoEditor() {
               {/* Bitiş tutamacı */}
               <div className="absolute top-0 bottom-0 w-3 bg-blue-600 cursor-ew-resize z-10" style={{ left: `${(timeRange.end / duration) * 100}%`, marginLeft: "0px" }} onMouseDown={handleTimelineHandleMouseDown("end")} onClick={(e) => e.stopPropagation()} />
 
+              {/* Saklanacak parçalar */}
+              {keptRanges.map((range, index) => (
+                <div key={index} className="absolute bottom-0 h-1.5 bg-green-500 pointer-events-none" style={{ left: `${(range.start / duration) * 100}%`, width: `${((range.end - range.start) / duration) * 100}%` }} />
+              ))}
+
               {/* Geçerli zaman göstergesi */}
               <div className="absolute top-0 h-full w-0.5 bg-white z-20 pointer-events-none" style={{ left: `${(currentTime / duration) * 100}%` }} />
             </div>
@@ -960,6 +1295,26 @@ export default function VideoEditor() {
                 <input type="text" value={formatTime(timeRange.end)} className="w-16 bg-gray-700 text-white px-1 py-0.5 text-xs rounded-md border border-gray-600 cursor-not-editable" readOnly />
               </div>
             </div>
+
+            {/* Birden fazla parça seçilirse aradaki kısımlar çıkarılır */}
+            <div className="flex flex-wrap items-center gap-1 mt-1 text-xs">
+              <button onClick={addKeptRange} className="bg-gray-700 hover:bg-gray-600 px-2 py-0.5 rounded-md">
+                Keep this part
+              </button>
+              {keptRanges.map((range, index) => (
+                <span key={index} className="bg-green-800 px-2 py-0.5 rounded-md">
+                  {formatTime(range.start)}–{formatTime(range.end)}
+                  <button onClick={() => setKeptRanges((current) => current.filter((_, i) => i !== index))} className="ml-1 text-gray-300">
+                    ×
+                  </button>
+                </span>
+              ))}
+              {keptRanges.length > 0 && (
+                <button onClick={() => setKeptRanges([])} className="text-gray-400 underline">
+                  Clear
+                </button>
+              )}
+            </div>
           </div>
         </div>
 
@@ -998,8 +1353,17 @@ export default function VideoEditor() {
                 </div>
               </div>
 
-              {/* Tahmini kalan süre (gerçek bir hesaplama yok) */}
-              <div className="text-center text-xs text-gray-400">Please wait while your video is being processed...</div>
+              <div className="text-center text-xs text-gray-400 mb-2">{paused ? "Paused" : "Please wait while your video is being processed..."}</div>
+
+              {/* İptal ve duraklatma */}
+              <div className="flex space-x-2">
+                <button className="flex-1 px-2 py-1 text-xs rounded-md bg-gray-600 hover:bg-gray-700" onClick={handlePauseToggle}>
+                  {paused ? "Resume" : "Pause"}
+                </button>
+                <button className="flex-1 px-2 py-1 text-xs rounded-md bg-red-600 hover:bg-red-700" onClick={handleCancel}>
+                  Cancel
+                </button>
+              </div>
             </div>
           ) : (
             <>
@@ -1056,13 +1420,137 @@ export default function VideoEditor() {
                   )}
                 </div>
 
+                {/* Platform ayarı seçimi */}
+                <div className="mb-3">
+                  <label className="text-sm text-gray-300 mb-1 block">Preset</label>
+                  <select value={presetId} onChange={(e) => selectPreset(e.target.value)} className="w-full bg-gray-700 text-sm rounded-md px-2 py-1">
+                    <option value="">None</option>
+                    {presets.map((p) => (
+                      <option key={p.id} value={p.id}>
+                        {p.name} ({p.maxSize} MB)
+                      </option>
+                    ))}
+                  </select>
+                  <div className="flex space-x-1 mt-1">
+                    <button onClick={importPresets} className="bg-gray-700 hover:bg-gray-600 px-2 py-1 rounded-md w-full text-xs">
+                      Import
+                    </button>
+                    <button onClick={exportPresets} className="bg-gray-700 hover:bg-gray-600 px-2 py-1 rounded-md w-full text-xs">
+                      Export
+                    </button>
+                  </div>
+                </div>
+
                 {/* Çıkış boyutu seçimi */}
                 <div className="mb-3">
                   <label className="text-sm text-gray-300 mb-1 block">Output Size (MB)</label>
                   <div className="flex items-center space-x-2">
-                    <input type="range" min="1" max="50" value={targetSize} onChange={(e) => setTargetSize(parseInt(e.target.value))} className="flex-grow h-2 bg-gray-700 rounded-lg appearance-none cursor-pointer" />
+                    <input type="range" min="1" max={selectedPreset ? selectedPreset.maxSize : 50} value={targetSize} onChange={(e) => setTargetSize(parseInt(e.target.value))} className="flex-grow h-2 bg-gray-700 rounded-lg appearance-none cursor-pointer" />
                     <span className="text-sm font-medium bg-gray-700 px-2 py-1 rounded-md min-w-[40px] text-center">{targetSize}</span>
                   </div>
+                  {!exportFormat && estimate?.streamCopy && <div className="text-xs text-green-400 mt-1">Already fits, it will be copied without re-encoding</div>}
+                  {!exportFormat && estimate && !estimate.streamCopy && (
+                    <div className="text-xs text-gray-400 mt-1">
+                      {estimate.format ? `${estimate.format.width}x${estimate.format.height}${estimate.format.fps ? ` @ ${estimate.format.fps}fps` : ""} · ` : ""}
+                      {Math.round(estimate.videoRate)}k video
+                      {estimate.audio ? ` · ${estimate.audio.bitrate}k ${estimate.audio.codec}` : ""} · {qualityLabels[estimate.quality]} quality
+                    </div>
+                  )}
+                  {!exportFormat && estimate && !estimate.feasible && (
+                    <div className="text-xs text-yellow-400 mt-1">
+                      {targetSize} MB is too small for this clip.
+                      {estimate.maxDuration != null && estimate.maxDuration > 0 && (
+                        <>
+                          {" "}
+                          About {estimate.maxDuration}s fits.{" "}
+                          <button onClick={trimToFit} className="underline">
+                            Trim to fit
+                          </button>
+                        </>
+                      )}
+                    </div>
+                  )}
+                </div>
+
+                {/* Çıktı türü */}
+                <div className="mb-3">
+                  <label className="text-sm text-gray-300 mb-1 block">Format</label>
+                  <select value={exportFormat} onChange={(e) => setExportFormat(e.target.value)} className="w-full bg-gray-700 text-sm rounded-md px-2 py-1">
+                    <option value="">Video</option>
+                    <option value="gif">GIF</option>
+                    <option value="webp">Animated WebP</option>
+                    <option value="sticker">Telegram sticker (WebM)</option>
+                    <option value="audio-mp3">Audio only (MP3)</option>
+                    <option value="audio-opus">Audio only (Opus)</option>
+                    <option value="audio-aac">Audio only (AAC)</option>
+                    <option value="audio-flac">Audio only (FLAC)</option>
+                  </select>
+                </div>
+
+                {/* Birden çok ses akışı varsa hangisinin alınacağı */}
+                {exportFormat.startsWith("audio-") && audioStreams.length > 1 && (
+                  <div className="mb-3">
+                    <label className="text-sm text-gray-300 mb-1 block">Audio stream</label>
+                    <select value={audioStream} onChange={(e) => setAudioStream(Number(e.target.value))} className="w-full bg-gray-700 text-sm rounded-md px-2 py-1">
+                      {audioStreams.map((stream, i) => (
+                        <option key={stream.index} value={i}>
+                          {`${i + 1}: ${stream.codec}${stream.language ? ` (${stream.language})` : ""}${stream.channels ? `, ${stream.channels} ch` : ""}`}
+                        </option>
+                      ))}
+                    </select>
+                  </div>
+                )}
+
+                {/* Codec seçimi */}
+                <div className="mb-3">
+                  <label className="text-sm text-gray-300 mb-1 block">Codec</label>
+                  <select value={codec} onChange={(e) => setCodec(e.target.value)} disabled={exportFormat !== ""} className="w-full bg-gray-700 text-sm rounded-md px-2 py-1 disabled:opacity-50">
+                    {availableCodecs.map((c) => (
+                      <option key={c} value={c}>
+                        {codecLabels[c] || c}
+                      </option>
+                    ))}
+                  </select>
+                </div>
+
+                {/* Ses codec seçimi */}
+                <div className="mb-3">
+                  <label className="text-sm text-gray-300 mb-1 block">Audio</label>
+                  <select value={audioCodec} onChange={(e) => setAudioCodec(e.target.value)} disabled={stripAudio || exportFormat !== ""} className="w-full bg-gray-700 text-sm rounded-md px-2 py-1 disabled:opacity-50">
+                    <option value="">Auto (fits the size)</option>
+                    <option value="aac" disabled={codec === "vp9"}>
+                      AAC
+                    </option>
+                    <option value="opus">Opus</option>
+                    <option value="mp3" disabled={codec === "vp9"}>
+                      MP3
+                    </option>
+                  </select>
+                  <label className="flex items-center gap-2 mt-2 text-sm text-gray-300">
+                    <input type="checkbox" checked={stripAudio} onChange={(e) => setStripAudio(e.target.checked)} />
+                    Remove audio
+                  </label>
+                </div>
+
+                {/* Çıktı ayarları */}
+                <div className="mb-3 space-y-2">
+                  <label className="text-sm text-gray-300 block">Save to</label>
+                  <select value={outputLocation} onChange={(e) => setOutputLocation(e.target.value)} className="w-full bg-gray-700 text-sm rounded-md px-2 py-1">
+                    <option value="fixed">{fixedDir ? "Chosen folder" : "Videos folder"}</option>
+                    <option value="sameFolder">Same folder as the video</option>
+                    <option value="ask">Ask every time</option>
+                  </select>
+                  {outputLocation === "fixed" && (
+                    <button onClick={chooseFixedDir} className="w-full bg-gray-700 hover:bg-gray-600 text-xs rounded-md px-2 py-1 truncate" title={fixedDir || ""}>
+                      {fixedDir || "Choose folder..."}
+                    </button>
+                  )}
+                  <input value={template} onChange={(e) => setTemplate(e.target.value)} title="{stem} {size} {codec} {ext} {date} {time} {duration}" className="w-full bg-gray-700 text-sm rounded-md px-2 py-1" />
+                  <select value={collision} onChange={(e) => setCollision(e.target.value)} className="w-full bg-gray-700 text-sm rounded-md px-2 py-1">
+                    <option value="autoIncrement">If the file exists: add a number</option>
+                    <option value="overwrite">If the file exists: overwrite</option>
+                    <option value="skip">If the file exists: skip</option>
+                  </select>
                 </div>
 
                 {/* İşlem Butonları */}
@@ -1074,6 +1562,10 @@ export default function VideoEditor() {
                     Process Video
                   </button>
 
+                  <button onClick={handleAddToQueue} className="bg-blue-600 hover:bg-blue-700 px-3 py-2 rounded-md w-full transition-colors text-sm">
+                    Add to Queue
+                  </button>
+
                   <button onClick={() => router("/")} className="bg-gray-600 hover:bg-gray-700 px-3 py-2 rounded-md w-full transition-colors text-sm">
                     Cancel
                   </button>
@@ -1,4 +1,5 @@
 import { Buffer } from "buffer";
+import { invoke } from "@tauri-apps/api/tauri";
 
 export function toBase64(str: string) {
   return Buffer.from(str, "binary").toString("base64");
@@ -7,3 +8,49 @@ export function toBase64(str: string) {
 export function fromBase64(str: string) {
   return Buffer.from(str, "base64").toString("binary");
 }
+
+// Dönüştürme işini iptal/duraklatma komutlarında tanımlamak için
+export function newJobId() {
+  return `job-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
+}
+
+// Backend'deki Settings ile aynı yapı
+export interface Settings {
+  version: number;
+  targetSize: number;
+  codec: string;
+  audioCodec: string | null;
+  stripAudio: boolean;
+  output: {
+    location: { kind: string; dir?: string | null };
+    template: string;
+    collision: string;
+  };
+  logLevel: string;
+}
+
+// Backend'deki Preset, platformun boyut ve format sınırları
+export interface Preset {
+  id: string;
+  name: string;
+  maxSize: number;
+  maxHeight: number | null;
+  maxDuration: number | null;
+  codec: string | null;
+  audioCodec: string | null;
+  stripAudio: boolean;
+  maxAudioBitrate: number | null;
+  builtin: boolean;
+}
+
+// Kaydedilmiş varsayılanlarla yeni bir dönüştürme isteği (input hariç)
+export async function requestDefaults() {
+  const settings = await invoke<Settings>("get_settings");
+  return {
+    targetSize: settings.targetSize,
+    codec: settings.codec,
+    audioCodec: settings.audioCodec,
+    stripAudio: settings.stripAudio,
+    output: settings.output,
+  };
+}